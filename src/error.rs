@@ -0,0 +1,156 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Exit status used by GNU numfmt when a number could not be converted.
+pub const EXIT_CONVERSION_FAILURE: i32 = 2;
+
+/// Everything that can go wrong while converting numbers.
+///
+/// Conversion errors carry the offending field text and its position:
+/// `line` and `column` are counted from 1, and 0 means "not known yet".
+#[derive(Debug)]
+pub enum NumfmtError {
+    InvalidNumber { field: String, line: usize, column: usize },
+    UnknownSuffix { field: String, line: usize, column: usize },
+    /// A suffix matching several prefixes with `--suffix-case=insensitive`.
+    AmbiguousSuffix { field: String, line: usize, column: usize },
+    /// A suffix was found while reading numbers with `--from=none`.
+    RejectedSuffix { field: String, line: usize, column: usize },
+    Overflow { field: String, line: usize, column: usize },
+    /// A non-zero number too small for a f64, like `1e-400`.
+    Underflow { field: String, line: usize, column: usize },
+    /// A value beyond the largest output suffix, or not a number at all.
+    OutputOverflow { field: String, line: usize, column: usize },
+    FieldOutOfRange { field: usize, line: usize },
+    /// Carries the diagnostic explaining what is wrong with the format.
+    InvalidFormatSpec(String),
+    /// Carries the diagnostic explaining what is wrong with the field list.
+    InvalidFieldSpec(String),
+    Io(io::Error),
+}
+
+impl NumfmtError {
+    /// Set the line number of a conversion error, used by callers iterating on lines.
+    pub fn with_line(mut self, number: usize) -> Self {
+        match &mut self {
+            NumfmtError::InvalidNumber { line, .. }
+            | NumfmtError::UnknownSuffix { line, .. }
+            | NumfmtError::AmbiguousSuffix { line, .. }
+            | NumfmtError::RejectedSuffix { line, .. }
+            | NumfmtError::Overflow { line, .. }
+            | NumfmtError::Underflow { line, .. }
+            | NumfmtError::OutputOverflow { line, .. }
+            | NumfmtError::FieldOutOfRange { line, .. } => *line = number,
+            _ => {}
+        }
+        self
+    }
+
+    /// Set the field text of a conversion error, used when only a part of it was parsed.
+    pub fn with_field(mut self, text: &str) -> Self {
+        match &mut self {
+            NumfmtError::InvalidNumber { field, .. }
+            | NumfmtError::UnknownSuffix { field, .. }
+            | NumfmtError::AmbiguousSuffix { field, .. }
+            | NumfmtError::RejectedSuffix { field, .. }
+            | NumfmtError::Overflow { field, .. }
+            | NumfmtError::Underflow { field, .. }
+            | NumfmtError::OutputOverflow { field, .. } => *field = text.to_string(),
+            _ => {}
+        }
+        self
+    }
+
+    /// Set the column of a conversion error, used once the field is located in its line.
+    pub fn with_column(mut self, number: usize) -> Self {
+        match &mut self {
+            NumfmtError::InvalidNumber { column, .. }
+            | NumfmtError::UnknownSuffix { column, .. }
+            | NumfmtError::AmbiguousSuffix { column, .. }
+            | NumfmtError::RejectedSuffix { column, .. }
+            | NumfmtError::Overflow { column, .. }
+            | NumfmtError::Underflow { column, .. }
+            | NumfmtError::OutputOverflow { column, .. } => *column = number,
+            _ => {}
+        }
+        self
+    }
+
+    /// Process exit status matching GNU numfmt semantics.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            NumfmtError::Io(_) => exitcode::IOERR,
+            NumfmtError::InvalidFormatSpec(_) | NumfmtError::InvalidFieldSpec(_) => exitcode::USAGE,
+            _ => EXIT_CONVERSION_FAILURE,
+        }
+    }
+}
+
+fn position(line: usize, column: usize) -> String {
+    match (line, column) {
+        (0, 0) => String::new(),
+        (0, c) => format!(" (column {})", c),
+        (l, 0) => format!(" (line {})", l),
+        (l, c) => format!(" (line {}, column {})", l, c),
+    }
+}
+
+impl fmt::Display for NumfmtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumfmtError::InvalidNumber { field, line, column } => {
+                write!(f, "invalid number: '{}'{}", field, position(*line, *column))
+            }
+            NumfmtError::UnknownSuffix { field, line, column } => {
+                write!(f, "invalid suffix in input: '{}'{}", field, position(*line, *column))
+            }
+            NumfmtError::AmbiguousSuffix { field, line, column } => write!(
+                f,
+                "ambiguous suffix in input: '{}' (milli or mega, pico or peta){}",
+                field,
+                position(*line, *column)
+            ),
+            NumfmtError::RejectedSuffix { field, line, column } => write!(
+                f,
+                "rejecting suffix in input: '{}'{} (consider using --from)",
+                field,
+                position(*line, *column)
+            ),
+            NumfmtError::Overflow { field, line, column } => {
+                write!(f, "value too large to be converted: '{}'{}", field, position(*line, *column))
+            }
+            NumfmtError::Underflow { field, line, column } => {
+                write!(f, "value too small to be converted: '{}'{}", field, position(*line, *column))
+            }
+            NumfmtError::OutputOverflow { field, line, column } => {
+                write!(f, "value too large to be printed: '{}'{}", field, position(*line, *column))
+            }
+            NumfmtError::FieldOutOfRange { field, line } => write!(
+                f,
+                "input line is too short, no numbers found to convert in field {}{}",
+                field,
+                position(*line, 0)
+            ),
+            NumfmtError::InvalidFormatSpec(message) | NumfmtError::InvalidFieldSpec(message) => {
+                write!(f, "{}", message)
+            }
+            NumfmtError::Io(err) => write!(f, "IO Error: {}", err),
+        }
+    }
+}
+
+impl Error for NumfmtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            NumfmtError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NumfmtError {
+    fn from(err: io::Error) -> Self {
+        NumfmtError::Io(err)
+    }
+}
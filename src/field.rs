@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+/// A parsed `--field` list, like `1,3-5,8-`.
+///
+/// Each range is inclusive and fields are counted from 1.
+/// Both `-` and an empty list select every field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelector {
+    ranges: Vec<(usize, usize)>,
+}
+
+fn parse_bound(bound: &str, list: &str) -> Result<usize, String> {
+    if bound.is_empty() || !bound.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("invalid field value '{}'", list));
+    }
+    match bound.parse::<usize>() {
+        Ok(0) => Err(String::from("fields are numbered from 1")),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("field number '{}' is too large", bound)),
+    }
+}
+
+impl FromStr for FieldSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(FieldSelector::all());
+        }
+        let mut ranges = Vec::new();
+        for range in s.split(',') {
+            let (start, end) = match range.split_once('-') {
+                None => {
+                    let n = parse_bound(range, s)?;
+                    (n, n)
+                }
+                Some(("", "")) => (1, usize::MAX),
+                Some(("", end)) => (1, parse_bound(end, s)?),
+                Some((start, "")) => (parse_bound(start, s)?, usize::MAX),
+                Some((start, end)) => (parse_bound(start, s)?, parse_bound(end, s)?),
+            };
+            if start > end {
+                return Err(format!("invalid decreasing range '{}'", range));
+            }
+            ranges.push((start, end));
+        }
+        Ok(FieldSelector { ranges })
+    }
+}
+
+impl FieldSelector {
+    /// Select every field of the line.
+    pub fn all() -> Self {
+        FieldSelector {
+            ranges: vec![(1, usize::MAX)],
+        }
+    }
+
+    /// Whether the field at `index`, counted from 1, should be converted.
+    pub fn contains(&self, index: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| start <= index && index <= end)
+    }
+
+    /// The first selected field, lines with fewer fields have nothing to convert.
+    pub fn first(&self) -> usize {
+        self.ranges.iter().map(|&(start, _)| start).min().unwrap_or(1)
+    }
+}
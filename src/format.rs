@@ -0,0 +1,159 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::padding;
+
+/// A parsed `--format` string, `%[0]['][-][N][.][N]f` surrounded by optional text.
+///
+/// `%%` stands for a literal percent sign in the text around the directive.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FormatSpec {
+    pub prefix: String,
+    pub suffix: String,
+    /// `'` flag, group the digits of the integer part.
+    pub grouping: bool,
+    /// `0` flag, pad the number itself with zeros up to `width`.
+    pub zero_padding: bool,
+    /// `-` flag, pad on the right instead of the left.
+    pub left_align: bool,
+    /// Minimum width, 0 when not given.
+    pub width: usize,
+    /// Number of decimals, `None` when not given.
+    pub precision: Option<usize>,
+}
+
+fn unescape_percent(text: &str) -> String {
+    text.replace("%%", "%")
+}
+
+impl FromStr for FormatSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        let mut spec = FormatSpec::default();
+
+        // text before the directive, skipping escaped percents
+        let mut i = 0;
+        loop {
+            match (bytes.get(i), bytes.get(i + 1)) {
+                (None, _) => return Err(format!("format '{}' has no % directive", s)),
+                (Some(b'%'), Some(b'%')) => i += 2,
+                (Some(b'%'), _) => break,
+                _ => i += 1,
+            }
+        }
+        spec.prefix = unescape_percent(&s[..i]);
+        i += 1;
+
+        // flags
+        loop {
+            match bytes.get(i) {
+                Some(b'\'') => spec.grouping = true,
+                Some(b'0') => spec.zero_padding = true,
+                Some(b'-') => spec.left_align = true,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        // width
+        let start = i;
+        while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        if i > start {
+            // like GNU numfmt, widths must fit in an int
+            spec.width = s[start..i]
+                .parse::<usize>()
+                .ok()
+                .filter(|&width| i32::try_from(width).is_ok())
+                .ok_or_else(|| format!("invalid format '{}' (width overflow)", s))?;
+        }
+
+        // precision
+        if bytes.get(i).is_none() {
+            return Err(format!("format '{}' ends in %", s));
+        }
+        if bytes.get(i) == Some(&b'.') {
+            i += 1;
+            let start = i;
+            while bytes.get(i).is_some_and(u8::is_ascii_digit) {
+                i += 1;
+            }
+            let precision = match &s[start..i] {
+                "" => 0,
+                digits => digits
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid precision in format '{}'", s))?,
+            };
+            spec.precision = Some(precision);
+        }
+
+        if bytes.get(i) != Some(&b'f') {
+            return Err(format!(
+                "invalid format '{}', directive must be %[0]['][-][N][.][N]f",
+                s
+            ));
+        }
+        i += 1;
+
+        // text after the directive
+        let mut j = i;
+        while j < bytes.len() {
+            match (bytes[j], bytes.get(j + 1)) {
+                (b'%', Some(b'%')) => j += 2,
+                (b'%', _) => return Err(format!("format '{}' has too many % directives", s)),
+                _ => j += 1,
+            }
+        }
+        spec.suffix = unescape_percent(&s[i..]);
+
+        Ok(spec)
+    }
+}
+
+impl FormatSpec {
+    /// Padding width given by the directive, negative when left aligned.
+    ///
+    /// Zero padding applies to the number only, so it gives no padding width.
+    pub fn padding(&self) -> Option<i64> {
+        let width = i64::try_from(self.width).ok()?;
+        match width {
+            0 => None,
+            _ if self.zero_padding && !self.left_align => None,
+            w if self.left_align => Some(-w),
+            w => Some(w),
+        }
+    }
+
+    /// Pad the number with zeros after its sign when the `0` flag is set.
+    pub fn zero_pad(&self, number: &str) -> String {
+        let length = number.chars().count();
+        if !self.zero_padding || self.left_align || length >= self.width {
+            return number.to_string();
+        }
+        let zeros = "0".repeat(self.width - length);
+        match number.strip_prefix('-') {
+            Some(digits) => format!("-{}{}", zeros, digits),
+            None => format!("{}{}", zeros, number),
+        }
+    }
+
+    /// Write a number, its unit and the user suffix with this format.
+    ///
+    /// `default_padding` is used when the directive has no width of its own.
+    pub fn render(&self, number: &str, unit: &str, suffix: &str, default_padding: i64) -> String {
+        format!(
+            "{}{}{}",
+            self.prefix,
+            padding(
+                &self.zero_pad(number),
+                unit,
+                suffix,
+                self.padding().unwrap_or(default_padding)
+            ),
+            self.suffix
+        )
+    }
+}
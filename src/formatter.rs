@@ -0,0 +1,653 @@
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::{
+    checked_power, get_exact_value, get_suffix_power, normalize_suffix_case, split_base_unit,
+    split_exponent, strip_number, to_iec_power, to_si_power,
+    validate_extended_suffix, validate_ieci_suffix, validate_si_suffix, validate_sub_unit_suffix,
+    FieldSelector,
+    BaseUnit, FormatSpec, InvalidMode, Locale, NumfmtError, NumfmtOptions, Quantity,
+    QuantityFormat, SuffixCase, Unit, EXTENDED_SI_SUFFIXES,
+};
+
+/// A number read from the input, before any output scaling.
+struct Parsed {
+    /// The number with its suffix applied.
+    value: f64,
+    /// The magnitude of the value when it is an integer, without the rounding errors of `f64`.
+    exact: Option<u128>,
+    /// Sign of the value, as `exact` only holds its magnitude.
+    negative: bool,
+    /// Number of decimals written in the input, 0 when a suffix scaled it.
+    precision: usize,
+    /// The exact value as a Kubernetes quantity, when there is one.
+    quantity: Option<Quantity>,
+    /// Suffix family of the input, kept with `--to=k8s`.
+    format: QuantityFormat,
+    /// Base unit written after the suffix, like `B` or `bit/s`, kept in the output.
+    unit: String,
+    /// Unit system of the input suffix, `Unit::None` without one, used by `--to=auto`.
+    system: Unit,
+}
+
+/// Converts numbers with a fixed set of options.
+///
+/// Every option is parsed and validated once by `Formatter::new`,
+/// so a single formatter can be reused for any number of lines.
+#[derive(Debug, Clone)]
+pub struct Formatter {
+    options: NumfmtOptions,
+    locale: Locale,
+    /// `None` when fields are separated by blanks.
+    delimiter: Option<String>,
+    fields: FieldSelector,
+    format: Option<FormatSpec>,
+    grouping: bool,
+    padding: i64,
+    precision: Option<usize>,
+    suffix: String,
+}
+
+impl Formatter {
+    /// Build a formatter writing `locale_decimal_point` and grouping digits by
+    /// threes with a comma, or with a dot when the comma is the decimal point,
+    /// see `Formatter::with_locale` for other conventions.
+    pub fn new(options: &NumfmtOptions, locale_decimal_point: &str) -> Result<Self, NumfmtError> {
+        let thousands_sep = if locale_decimal_point == "," { "." } else { "," };
+        let locale = Locale {
+            decimal_point: locale_decimal_point.to_string(),
+            thousands_sep: thousands_sep.to_string(),
+            grouping: vec![3],
+        };
+        Formatter::with_locale(options, &locale)
+    }
+
+    /// Build a formatter reading and writing numbers with the conventions of `locale`.
+    pub fn with_locale(options: &NumfmtOptions, locale: &Locale) -> Result<Self, NumfmtError> {
+        let format = match &options.format {
+            Some(format) => Some(
+                format
+                    .parse::<FormatSpec>()
+                    .map_err(NumfmtError::InvalidFormatSpec)?,
+            ),
+            None => None,
+        };
+        let grouping = options.grouping || format.as_ref().is_some_and(|spec| spec.grouping);
+        // the precision of the format has priority over --precision
+        let precision = format
+            .as_ref()
+            .and_then(|spec| spec.precision)
+            .or(options.precision);
+
+        let fields = options
+            .field
+            .parse::<FieldSelector>()
+            .map_err(NumfmtError::InvalidFieldSpec)?;
+
+        let mut locale = locale.clone();
+        if let Some(separator) = &options.grouping_separator {
+            locale.thousands_sep = separator.clone();
+            if locale.grouping.is_empty() {
+                locale.grouping = vec![3];
+            }
+        }
+
+        Ok(Formatter {
+            options: options.clone(),
+            locale,
+            delimiter: options.delimiter.clone().filter(|d| !d.is_empty()),
+            fields,
+            format,
+            grouping,
+            padding: options.padding.unwrap_or(1),
+            precision,
+            suffix: options.suffix.clone().unwrap_or_default(),
+        })
+    }
+
+    pub fn options(&self) -> &NumfmtOptions {
+        &self.options
+    }
+
+    fn parse(&self, field: &str) -> Result<Parsed, NumfmtError> {
+        if self.options.from == Unit::K8s {
+            return self.parse_quantity(field);
+        }
+        let mut suffix = String::new();
+        let invalid = || NumfmtError::InvalidNumber { field: field.to_string(), line: 0, column: 0 };
+        let ungrouped = if self.options.accept_grouping {
+            self.locale.ungroup(field).ok_or_else(invalid)?
+        } else {
+            field.to_string()
+        };
+
+        // convert string to number
+        // a literal "." is not a decimal point in locales such as de_DE, so it is
+        // turned into a character no number holds before the decimal point is mapped
+        let mut number = if self.locale.decimal_point == "." {
+            ungrouped
+        } else {
+            ungrouped.replace('.', "\u{fffd}").replace(self.locale.decimal_point.as_str(), ".")
+        };
+        let res = match strip_number(&mut number, &mut suffix) {
+            Ok(n) => n,
+            Err(_) => return Err(invalid()),
+        };
+        if !res.is_finite() {
+            return Err(NumfmtError::Overflow { field: field.to_string(), line: 0, column: 0 });
+        }
+        // the user suffix is optional in input numbers
+        if !self.suffix.is_empty() && suffix.ends_with(self.suffix.as_str()) {
+            suffix.truncate(suffix.len() - self.suffix.len());
+        }
+        // without --from there is no prefix, so no base unit to read either
+        let (prefix, unit) = match self.options.from {
+            Unit::None => (suffix.as_str(), ""),
+            _ => split_base_unit(&suffix),
+        };
+        let (prefix, unit) = (prefix.to_string(), unit.to_string());
+        suffix = prefix;
+        if self.options.suffix_case == SuffixCase::Insensitive {
+            suffix = normalize_suffix_case(self.options.from, &suffix).ok_or_else(|| NumfmtError::AmbiguousSuffix {
+                field: field.to_string(),
+                line: 0,
+                column: 0,
+            })?;
+        }
+        let extended = self.options.extended_prefixes;
+        let known_suffix = match self.options.from {
+            Unit::None if suffix.is_empty() => true,
+            Unit::None => {
+                return Err(NumfmtError::RejectedSuffix { field: field.to_string(), line: 0, column: 0 });
+            }
+            Unit::Si => {
+                validate_si_suffix(&suffix)
+                    || validate_sub_unit_suffix(&suffix)
+                    || (extended && EXTENDED_SI_SUFFIXES.contains(&suffix.as_str()))
+            }
+            Unit::Auto => {
+                validate_si_suffix(&suffix)
+                    || validate_ieci_suffix(&suffix)
+                    || validate_sub_unit_suffix(&suffix)
+                    || (extended && validate_extended_suffix(&suffix))
+            }
+            Unit::Iec | Unit::IecI => {
+                validate_si_suffix(&suffix)
+                    || validate_ieci_suffix(&suffix)
+                    || (extended && validate_extended_suffix(&suffix))
+            }
+            Unit::K8s => unreachable!("quantities are parsed by parse_quantity"),
+        };
+        if !known_suffix {
+            return Err(NumfmtError::UnknownSuffix { field: field.to_string(), line: 0, column: 0 });
+        }
+
+        // determine base and power of number
+        let (base, power) = get_suffix_power(self.options.from, &suffix);
+        if self.options.debug {
+            eprintln!("numfmt: '{}' read as {} * {}^{}", field, res, base, power);
+        }
+        let overflow = || NumfmtError::Overflow { field: field.to_string(), line: 0, column: 0 };
+        let factor = match power {
+            p if p < 0 => f64::from(base).powi(p),
+            p => checked_power(base, p as u32).ok_or_else(overflow)? as f64,
+        };
+        let value = res * factor * self.options.from_unit as f64;
+        if !value.is_finite() {
+            return Err(overflow());
+        }
+        let (mantissa, exponent) = split_exponent(&number);
+        if value == 0.0 && mantissa.contains(|c: char| ('1'..='9').contains(&c)) {
+            return Err(NumfmtError::Underflow { field: field.to_string(), line: 0, column: 0 });
+        }
+
+        // sub-units keep the decimals they stand for, like 250m read as 0.250,
+        // and so do exponents, like 25e-4 read as 0.0025
+        let written = mantissa.find('.').map_or(0, |i| mantissa.len() - i - 1);
+        // no f64 needs more decimals than this
+        let decimals = (written as i64 - i64::from(exponent)).clamp(0, 1074) as usize;
+        let precision = match power {
+            _ if suffix.is_empty() => decimals,
+            p if p < 0 => decimals + p.unsigned_abs() as usize,
+            _ => 0,
+        };
+
+        let exact = get_exact_value(&number, base, power, self.options.from_unit);
+        let negative = number.starts_with('-');
+        let system = match base {
+            _ if suffix.is_empty() => Unit::None,
+            2 if suffix.ends_with('i') => Unit::IecI,
+            2 => Unit::Iec,
+            _ => Unit::Si,
+        };
+        let format = match (base, power) {
+            (2, p) if p > 0 => QuantityFormat::BinarySI,
+            _ => QuantityFormat::DecimalSI,
+        };
+        Ok(Parsed {
+            value,
+            exact,
+            negative,
+            precision,
+            quantity: exact
+                .and_then(|value| i128::try_from(value).ok())
+                .and_then(|value| value.checked_mul(if negative { -1_000_000_000 } else { 1_000_000_000 }))
+                .map(|nanos| Quantity { nanos, format }),
+            format,
+            unit,
+            system,
+        })
+    }
+
+    fn convert_base_unit(&self, mut parsed: Parsed) -> Parsed {
+        /*
+        Switch a number written in bits to bytes or the other way around,
+        keeping a trailing /s. Other base units are left as they are.
+        */
+        let target = match self.options.to_base_unit {
+            Some(target) => target,
+            None => return parsed,
+        };
+        let rate = parsed.unit.ends_with("/s") || parsed.unit == "bps";
+        let source = match parsed.unit.trim_end_matches("/s") {
+            "B" | "byte" | "bytes" => BaseUnit::Bytes,
+            "b" | "bit" | "bits" | "bps" => BaseUnit::Bits,
+            _ => return parsed,
+        };
+        if source == target {
+            return parsed;
+        }
+        let unit = match target {
+            BaseUnit::Bits => "bit",
+            BaseUnit::Bytes => "B",
+        };
+        parsed.unit = if rate { format!("{}/s", unit) } else { unit.to_string() };
+
+        if target == BaseUnit::Bits {
+            parsed.value *= 8.0;
+            parsed.exact = parsed.exact.and_then(|value| value.checked_mul(8));
+            parsed.quantity = parsed.quantity.and_then(|mut quantity| {
+                quantity.nanos = quantity.nanos.checked_mul(8)?;
+                Some(quantity)
+            });
+            return parsed;
+        }
+        // dividing by 8 needs up to 3 more decimals, like 100b to 12.5B
+        parsed.value /= 8.0;
+        parsed.precision += match parsed.exact {
+            Some(value) => 3 - value.trailing_zeros().min(3) as usize,
+            None => 3,
+        };
+        parsed.exact = parsed.exact.filter(|value| value % 8 == 0).map(|value| value / 8);
+        parsed.quantity = parsed.quantity.filter(|quantity| quantity.nanos % 8 == 0).map(|mut quantity| {
+            quantity.nanos /= 8;
+            quantity
+        });
+        parsed
+    }
+
+    fn parse_quantity(&self, field: &str) -> Result<Parsed, NumfmtError> {
+        // the user suffix is optional in input numbers
+        let number = match field.strip_suffix(self.suffix.as_str()) {
+            Some(number) if !self.suffix.is_empty() => number,
+            _ => field,
+        };
+        let mut quantity = number
+            .parse::<Quantity>()
+            .map_err(|err| err.with_field(field))?;
+        if self.options.debug {
+            eprintln!("numfmt: '{}' read as {} nanos", field, quantity.nanos);
+        }
+        let value = quantity.value() * self.options.from_unit as f64;
+        let scaled = i128::try_from(self.options.from_unit)
+            .ok()
+            .and_then(|unit_size| quantity.nanos.checked_mul(unit_size));
+        let negative = quantity.nanos < 0;
+        let exact = scaled
+            .filter(|nanos| nanos % 1_000_000_000 == 0)
+            .map(|nanos| (nanos / 1_000_000_000).unsigned_abs());
+        let precision = quantity.precision();
+        let format = quantity.format;
+        let system = match format {
+            _ if !number.ends_with(char::is_alphabetic) => Unit::None,
+            QuantityFormat::BinarySI => Unit::IecI,
+            _ => Unit::Si,
+        };
+        let quantity = scaled.map(|nanos| {
+            quantity.nanos = nanos;
+            quantity
+        });
+        Ok(Parsed {
+            value,
+            exact,
+            negative,
+            precision,
+            quantity,
+            format,
+            unit: String::new(),
+            system,
+        })
+    }
+
+    /// Read a human readable number such as `1.5K` according to `--from` and `--from-unit`.
+    pub fn parse_human(&self, number: &str) -> Result<f64, NumfmtError> {
+        Ok(self.parse(number)?.value)
+    }
+
+    /// Write a value according to `--to`, `--to-unit`, `--precision`, `--grouping`,
+    /// `--format` and `--padding`.
+    ///
+    /// Unless a precision is given, scaled values below 10 get one decimal
+    /// (like `1.5K`) and any other value none, as GNU numfmt does.
+    /// The value is rounded with `--round` at that precision, after scaling.
+    ///
+    /// With `--to=auto` there is no input suffix to follow, so the value is not scaled.
+    /// Values that are not finite, or too large for the largest suffix, are an
+    /// `OutputOverflow` error.
+    pub fn format_value(&self, value: f64) -> Result<String, NumfmtError> {
+        let to = match self.options.to {
+            Unit::Auto => Unit::None,
+            to => to,
+        };
+        self.format_padded(value, to, "", self.padding)
+    }
+
+    fn format_padded(
+        &self,
+        value: f64,
+        to: Unit,
+        base_unit: &str,
+        padding: i64,
+    ) -> Result<String, NumfmtError> {
+        let overflow = || NumfmtError::OutputOverflow { field: value.to_string(), line: 0, column: 0 };
+        let value = value / self.options.to_unit as f64;
+        if !value.is_finite() {
+            return Err(overflow());
+        }
+        if to == Unit::K8s {
+            return match Quantity::from_f64(value, QuantityFormat::DecimalSI) {
+                Some(quantity) => Ok(self.render_quantity(quantity, base_unit, padding)),
+                None => Err(overflow()),
+            };
+        }
+        let (step, power_step) = match to {
+            Unit::Iec | Unit::IecI => (1024.0, 10),
+            _ => (1000.0, 3),
+        };
+        // the largest suffix is Y, or Q with --extended-prefixes
+        let max_power = if self.options.extended_prefixes { 10 } else { 8 } * power_step;
+        // and the smallest is f with --sub-units, for SI only
+        let min_power = match to {
+            Unit::Si if self.options.sub_units => -5 * power_step,
+            _ => 0,
+        };
+        let scaled = to != Unit::None;
+        let (mut number, mut power) = if scaled {
+            scale(value, step, power_step, (min_power, max_power))
+        } else {
+            (value, 0)
+        };
+        let default_precision = |number: f64, power: i32| match self.precision {
+            Some(precision) => precision,
+            None if power != 0 && number.abs() < 10.0 => 1,
+            None => 0,
+        };
+
+        // like GNU numfmt, values below 10 are rounded at one decimal,
+        // which is only printed when they were scaled: 9.01 is written 9
+        let round_precision = match self.precision {
+            Some(precision) => precision,
+            None if scaled && number.abs() < 10.0 => 1,
+            None => 0,
+        };
+        number = self.options.round.round(number, round_precision);
+        // rounding up can reach the next suffix, like 999.9 to 1.0K
+        if scaled && number.abs() >= step && power < max_power {
+            number /= step;
+            power += power_step;
+        }
+        // like GNU numfmt, values beyond the largest suffix are not printed
+        if scaled && number.abs() >= step {
+            return Err(overflow());
+        }
+        // or stop needing a decimal, like 9.96K to 10K
+        let precision = default_precision(number, power);
+        let unit = match to {
+            Unit::Iec => to_iec_power(false, &2, &mut power),
+            Unit::IecI => to_iec_power(true, &2, &mut power),
+            Unit::None => String::new(),
+            _ => to_si_power(&10, &mut power),
+        };
+        let mut res = format!("{:.*}", precision, number);
+        // a value written as zero has no sign, like -0.5 written 0
+        if res.starts_with('-') && res[1..].chars().all(|c| c == '0' || c == '.') {
+            res.remove(0);
+        }
+        Ok(self.render(res, &(unit + base_unit), padding))
+    }
+
+    /// Convert a single field according to `--from` and `--to`.
+    ///
+    /// With `--to=none` the number is not scaled and keeps the decimals
+    /// written in the input, unless an input suffix scaled it.
+    pub fn convert(&self, field: &str) -> Result<String, NumfmtError> {
+        self.convert_padded(field, self.padding)
+    }
+
+    fn convert_padded(&self, field: &str, padding: i64) -> Result<String, NumfmtError> {
+        let parsed = self.convert_base_unit(self.parse(field)?);
+        // --to=auto writes each number in the unit system of its suffix
+        let to = match self.options.to {
+            Unit::Auto => parsed.system,
+            to => to,
+        };
+        match to {
+            Unit::None => {
+                let precision = self.precision.unwrap_or(parsed.precision);
+                let to_unit = self.options.to_unit;
+                // integers are written exactly when the output unit divides them
+                if let Some(value) = parsed.exact.filter(|value| value % to_unit == 0) {
+                    let value = value / to_unit;
+                    let sign = if parsed.negative && value != 0 { "-" } else { "" };
+                    let number = match precision {
+                        0 => format!("{}{}", sign, value),
+                        _ => format!("{}{}.{}", sign, value, "0".repeat(precision)),
+                    };
+                    return Ok(self.render(number, &parsed.unit, padding));
+                }
+                let number = self.options.round.round(parsed.value / to_unit as f64, precision);
+                Ok(self.render(format!("{:.*}", precision, number), &parsed.unit, padding))
+            }
+            Unit::K8s => {
+                let quantity = match parsed.quantity {
+                    Some(quantity) if self.options.to_unit == 1 => Some(quantity),
+                    _ => Quantity::from_f64(parsed.value / self.options.to_unit as f64, parsed.format),
+                };
+                match quantity {
+                    Some(quantity) => Ok(self.render_quantity(quantity, &parsed.unit, padding)),
+                    None => Err(NumfmtError::OutputOverflow { field: field.to_string(), line: 0, column: 0 }),
+                }
+            }
+            _ => self
+                .format_padded(parsed.value, to, &parsed.unit, padding)
+                .map_err(|err| err.with_field(field)),
+        }
+    }
+
+    fn render_quantity(&self, quantity: Quantity, base_unit: &str, padding: i64) -> String {
+        let (number, suffix) = quantity.canonical();
+        self.render(number, &(suffix + base_unit), padding)
+    }
+
+    fn render(&self, mut res: String, res_unit: &str, padding: i64) -> String {
+        // convert to exporting format
+        if self.locale.decimal_point != "." {
+            res = res.replacen('.', &self.locale.decimal_point, 1);
+        }
+        if self.grouping && !self.locale.thousands_sep.is_empty() {
+            // only the integer part is grouped, after the sign
+            let start = res.find(|c: char| c.is_ascii_digit()).unwrap_or(res.len());
+            let end = res[start..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(res.len(), |i| start + i);
+            let digits = self.locale.group_digits(&res[start..end]);
+            res = format!("{}{}{}", &res[..start], digits, &res[end..]);
+        }
+
+        // format has higher priority because it include padding functionnalities
+        match &self.format {
+            Some(spec) => spec.render(&res, res_unit, &self.suffix, padding),
+            None => crate::padding(&res, res_unit, &self.suffix, padding),
+        }
+    }
+
+    /// Convert the selected fields of one line and write it to `writer`.
+    ///
+    /// With `--invalid=abort` the first conversion error is returned,
+    /// otherwise invalid fields are written unchanged and the errors
+    /// that should be diagnosed are returned instead.
+    ///
+    /// Lines too short to hold the first selected field are written unchanged,
+    /// with a `FieldOutOfRange` warning under `--debug` only.
+    pub fn process_line<W: Write>(
+        &self,
+        line: &str,
+        writer: &mut W,
+    ) -> Result<Vec<NumfmtError>, NumfmtError> {
+        let mut warnings = Vec::new();
+
+        let mut index = 0;
+        let mut column = 1;
+        let mut rest = Some(line);
+        while let Some(text) = rest {
+            let (field, next) = self.next_field(text);
+            index += 1;
+            let number = field.trim_start_matches(is_blank);
+            if self.fields.contains(index) && !number.is_empty() {
+                // if the field is one of those asked by the user, format it
+                match self.convert_padded(number, self.field_padding(field, index)) {
+                    Ok(res) => write!(writer, "{}", res)?,
+                    Err(err) => {
+                        let err = err.with_column(column + field.len() - number.len());
+                        handle_invalid(err, self.options.invalid, &mut warnings)?;
+                        write!(writer, "{}", field)?;
+                    }
+                };
+            } else {
+                write!(writer, "{}", field)?;
+            }
+            column += field.chars().count();
+            rest = match next {
+                Some((separator, tail)) => {
+                    write!(writer, "{}", separator)?;
+                    column += separator.chars().count();
+                    Some(tail)
+                }
+                None => None,
+            };
+        }
+        // write newline
+        write!(writer, "{}", if self.options.zero_terminated { "\0" } else { "\n" })?;
+
+        // like GNU numfmt, short lines are not a conversion failure
+        let first = self.fields.first();
+        if self.options.debug && !line.is_empty() && index < first {
+            warnings.push(NumfmtError::FieldOutOfRange { field: first, line: 0 });
+        }
+        Ok(warnings)
+    }
+
+    fn next_field<'a>(&self, text: &'a str) -> (&'a str, Option<(&'a str, &'a str)>) {
+        /*
+        Split the next field from the text, along with the separator and
+        the remaining text. With the default delimiter, the blanks before
+        a field belong to it and a single blank separates fields, like GNU numfmt.
+        With --accept-grouping in a locale grouping digits with blanks, digit
+        groups after a space stay in the field, like 1 234 567 in fr_FR.
+        */
+        match &self.delimiter {
+            Some(delimiter) => match text.find(delimiter.as_str()) {
+                Some(end) => {
+                    let tail = end + delimiter.len();
+                    (&text[..end], Some((&text[end..tail], &text[tail..])))
+                }
+                None => (text, None),
+            },
+            None => {
+                let start = text.len() - text.trim_start_matches(is_blank).len();
+                let mut end = match text[start..].find(is_blank) {
+                    Some(i) => start + i,
+                    None => return (text, None),
+                };
+                let separator = &self.locale.thousands_sep;
+                let blank_groups = self.options.accept_grouping
+                    && !separator.is_empty()
+                    && separator.chars().all(char::is_whitespace);
+                if let Some(&size) = self.locale.grouping.first().filter(|_| blank_groups) {
+                    while text[end..].starts_with(' ') && text[..end].ends_with(|c: char| c.is_ascii_digit()) {
+                        let group = &text[end + 1..];
+                        let digits = group.len() - group.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                        if digits != size {
+                            break;
+                        }
+                        match group.find(is_blank) {
+                            Some(i) => end += 1 + i,
+                            None => return (text, None),
+                        }
+                    }
+                }
+                (&text[..end], Some((&text[end..end + 1], &text[end + 1..])))
+            }
+        }
+    }
+
+    fn field_padding(&self, field: &str, index: usize) -> i64 {
+        /*
+        Without an explicit width, fields separated by blanks are padded
+        to their original width, so converting columns keeps them aligned.
+        */
+        let explicit = self.options.padding.is_some()
+            || self.format.as_ref().is_some_and(|spec| spec.padding().is_some());
+        if explicit || self.delimiter.is_some() || (index == 1 && !field.starts_with(is_blank)) {
+            return self.padding;
+        }
+        field.chars().count() as i64
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn scale(mut number: f64, step: f64, power_step: i32, limits: (i32, i32)) -> (f64, i32) {
+    /*
+    Divide or multiply the number by the step of the unit system until it fits,
+    stopping at the largest and smallest suffixes allowed.
+    */
+    let (min_power, max_power) = limits;
+    let mut power = 0;
+    while number.abs() >= step && power < max_power {
+        number /= step;
+        power += power_step;
+    }
+    while number != 0.0 && number.abs() < 1.0 && power > min_power {
+        number *= step;
+        power -= power_step;
+    }
+    (number, power)
+}
+
+fn handle_invalid(
+    err: NumfmtError,
+    invalid_mode: InvalidMode,
+    warnings: &mut Vec<NumfmtError>,
+) -> Result<(), NumfmtError> {
+    match (err, invalid_mode) {
+        (err @ NumfmtError::Io(_), _) | (err, InvalidMode::Abort) => Err(err),
+        (err, InvalidMode::Fail) | (err, InvalidMode::Warn) => {
+            warnings.push(err);
+            Ok(())
+        }
+        (_, InvalidMode::Ignore) => Ok(()),
+    }
+}
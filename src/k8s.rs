@@ -0,0 +1,204 @@
+use std::str::FromStr;
+
+use crate::NumfmtError;
+
+const NANOS: i128 = 1_000_000_000;
+const DECIMAL_SUFFIXES: [(&str, i32); 10] = [
+    ("n", -9),
+    ("u", -6),
+    ("m", -3),
+    ("", 0),
+    ("k", 3),
+    ("M", 6),
+    ("G", 9),
+    ("T", 12),
+    ("P", 15),
+    ("E", 18),
+];
+const BINARY_SUFFIXES: [&str; 7] = ["", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei"];
+
+/// Suffix family of a Kubernetes quantity, kept when it is written back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantityFormat {
+    /// `Ki`, `Mi`, ... up to `Ei`.
+    BinarySI,
+    /// `n`, `u`, `m`, `k`, `M`, ... up to `E`.
+    DecimalSI,
+    /// `e3`, `E-6`, ...
+    DecimalExponent,
+}
+
+/// A Kubernetes resource quantity such as `512Mi`, `250m` or `1e3`.
+///
+/// The value is held exactly as a number of nanos, amounts below
+/// one nano are rounded up like Kubernetes does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quantity {
+    pub nanos: i128,
+    pub format: QuantityFormat,
+}
+
+fn invalid(s: &str) -> NumfmtError {
+    NumfmtError::InvalidNumber { field: s.to_string(), line: 0, column: 0 }
+}
+
+fn overflow(s: &str) -> NumfmtError {
+    NumfmtError::Overflow { field: s.to_string(), line: 0, column: 0 }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn divide_away_from_zero(value: i128, divisor: i128) -> i128 {
+    match value % divisor {
+        0 => value / divisor,
+        _ => value / divisor + value.signum(),
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = NumfmtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        /*
+        Follow the quantity grammar of Kubernetes:
+        [+-] (digits | digits. | .digits | digits.digits) (binarySI | decimalSI | [eE] [+-] digits)
+        */
+        let (negative, rest) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (int, rest) = split_digits(rest);
+        let (frac, suffix) = match rest.strip_prefix('.') {
+            Some(rest) => split_digits(rest),
+            None => ("", rest),
+        };
+        if int.is_empty() && frac.is_empty() {
+            return Err(invalid(s));
+        }
+
+        let exponent_suffix = suffix
+            .strip_prefix(|c| c == 'e' || c == 'E')
+            .filter(|exp| {
+                let digits = exp.strip_prefix(|c| c == '+' || c == '-').unwrap_or(exp);
+                !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+            });
+        let (format, exponent, binary_power) = if let Some(exp) = exponent_suffix {
+            let exp = exp.parse::<i32>().map_err(|_| overflow(s))?;
+            (QuantityFormat::DecimalExponent, exp, 0)
+        } else if let Some(&(_, exp)) = DECIMAL_SUFFIXES.iter().find(|(x, _)| *x == suffix) {
+            (QuantityFormat::DecimalSI, exp, 0)
+        } else if let Some(power) = BINARY_SUFFIXES.iter().position(|x| *x == suffix) {
+            (QuantityFormat::BinarySI, 0, power as u32)
+        } else {
+            return Err(NumfmtError::UnknownSuffix { field: s.to_string(), line: 0, column: 0 });
+        };
+
+        let digits = format!("{}{}", int, frac)
+            .parse::<i128>()
+            .map_err(|_| overflow(s))?;
+        let mantissa = digits
+            .checked_mul(1024_i128.pow(binary_power))
+            .ok_or_else(|| overflow(s))?;
+        let shift = exponent
+            .checked_add(9 - frac.len() as i32)
+            .ok_or_else(|| overflow(s))?;
+        let nanos = match shift {
+            0 => mantissa,
+            // too small amounts are rounded up to one nano
+            _ if shift < 0 => match 10_i128.checked_pow(shift.unsigned_abs()) {
+                Some(divisor) => divide_away_from_zero(mantissa, divisor),
+                None if mantissa == 0 => 0,
+                None => mantissa.signum(),
+            },
+            _ if mantissa == 0 => 0,
+            _ => 10_i128
+                .checked_pow(shift as u32)
+                .and_then(|factor| mantissa.checked_mul(factor))
+                .ok_or_else(|| overflow(s))?,
+        };
+        Ok(Quantity {
+            nanos: if negative { -nanos } else { nanos },
+            format,
+        })
+    }
+}
+
+impl Quantity {
+    /// Build a quantity from a number, rounded up to the nano.
+    pub fn from_f64(value: f64, format: QuantityFormat) -> Option<Self> {
+        let nanos = value * NANOS as f64;
+        if !nanos.is_finite() || nanos.abs() >= i128::MAX as f64 {
+            return None;
+        }
+        let nanos = if nanos < 0.0 { nanos.floor() } else { nanos.ceil() };
+        Some(Quantity {
+            nanos: nanos as i128,
+            format,
+        })
+    }
+
+    pub fn value(&self) -> f64 {
+        self.nanos as f64 / NANOS as f64
+    }
+
+    /// Number of decimals needed to write the value exactly.
+    pub fn precision(&self) -> usize {
+        let mut fraction = (self.nanos % NANOS).abs();
+        if fraction == 0 {
+            return 0;
+        }
+        let mut precision = 9;
+        while fraction % 10 == 0 {
+            fraction /= 10;
+            precision -= 1;
+        }
+        precision
+    }
+
+    /// Canonical form of the quantity, split into its number and suffix.
+    ///
+    /// Like Kubernetes, the largest suffix giving an integer is used, and
+    /// binary quantities below 1024 or with a fractional part are written
+    /// with decimal suffixes instead.
+    pub fn canonical(&self) -> (String, String) {
+        if self.format == QuantityFormat::BinarySI
+            && self.nanos.abs() >= 1024 * NANOS
+            && self.nanos % NANOS == 0
+        {
+            let mut number = self.nanos / NANOS;
+            let mut power = 0;
+            while power < BINARY_SUFFIXES.len() - 1 && number % 1024 == 0 {
+                number /= 1024;
+                power += 1;
+            }
+            return (number.to_string(), BINARY_SUFFIXES[power].to_string());
+        }
+
+        if self.nanos == 0 {
+            return ("0".to_string(), String::new());
+        }
+        let mut number = self.nanos;
+        let mut exponent = -9;
+        let max_exponent = match self.format {
+            QuantityFormat::DecimalExponent => i32::MAX,
+            _ => 18,
+        };
+        while exponent < max_exponent && number % 1000 == 0 {
+            number /= 1000;
+            exponent += 3;
+        }
+        let suffix = match self.format {
+            QuantityFormat::DecimalExponent if exponent == 0 => String::new(),
+            QuantityFormat::DecimalExponent => format!("e{}", exponent),
+            _ => DECIMAL_SUFFIXES
+                .iter()
+                .find(|&&(_, exp)| exp == exponent)
+                .map_or_else(String::new, |(suffix, _)| suffix.to_string()),
+        };
+        (number.to_string(), suffix)
+    }
+}
@@ -1,8 +1,22 @@
 use core::cmp::min;
-use std::error::Error;
 
-use clap::ArgMatches;
-use regex::Regex;
+mod error;
+mod field;
+mod format;
+mod formatter;
+mod k8s;
+mod locale;
+mod options;
+
+pub use error::{NumfmtError, EXIT_CONVERSION_FAILURE};
+pub use field::FieldSelector;
+pub use format::FormatSpec;
+pub use formatter::Formatter;
+pub use k8s::{Quantity, QuantityFormat};
+pub use locale::Locale;
+pub use options::{
+    BaseUnit, InvalidMode, NumfmtOptions, NumfmtOptionsBuilder, RoundMethod, SuffixCase, Unit,
+};
 
 pub trait ModuloSignedExt {
     fn modulo(&self, n: Self) -> Self;
@@ -19,11 +33,17 @@ macro_rules! modulo_signed_ext_impl {
 }
 modulo_signed_ext_impl! { usize i8 i16 i32 i64 }
 
-
 pub const DIGITS: &str = "0123456789";
-pub const DIGITALS: &str = "0123456789.,";
-pub const IEC_SUFFIXES: [&'static str; 9] = ["","Ki","Mi","Gi","Ti","Pi","Ei","Zi","Yi"];
-pub const SI_SUFFIXES: [&'static str; 9] = ["","K","M","G","T","P","E","Z","Y"];
+pub const DIGITALS: &str = "0123456789.";
+pub const IEC_SUFFIXES: [&str; 9] = ["","Ki","Mi","Gi","Ti","Pi","Ei","Zi","Yi"];
+pub const SI_SUFFIXES: [&str; 9] = ["","K","M","G","T","P","E","Z","Y"];
+/// Ronna and quetta, added by the 2022 SI revision and unknown to GNU numfmt.
+pub const EXTENDED_SI_SUFFIXES: [&str; 2] = ["R", "Q"];
+pub const EXTENDED_IEC_SUFFIXES: [&str; 2] = ["Ri", "Qi"];
+/// Milli, micro (written u or µ), nano, pico and femto.
+pub const SUB_UNIT_SUFFIXES: [&str; 6] = ["m", "u", "µ", "n", "p", "f"];
+/// Base units written after the suffix, like the B of `1.5GB`, optionally followed by `/s`.
+pub const BASE_UNITS: [&str; 8] = ["bytes", "byte", "bits", "bit", "bps", "Hz", "B", "b"];
 pub const DEFAULT_FIELD: &str = "1-18446744073709551615";
 
 pub fn is_int(s: String) -> Result<(), String> {
@@ -43,139 +63,254 @@ pub fn strick_positive_int(s: String) -> Result<(), String> {
     }
 }
 
-pub fn validate_field(s: String) -> Result<(), String> {
-    let mut res = s
-        .split("-")
-        .into_iter()
-        .all(|sub| sub.chars().all(|c| DIGITS.contains(c)));
-    res &= s.split("-").count() <= 2;
-    match res {
-        true => Ok(()),
-        false => Err(String::from("invalid arg for field")),
+pub fn positive_int(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(String::from("value should be a positive integer.")),
     }
 }
 
-pub fn validate_format(s: String) -> Result<(), String> {
-    let start = s.find("%").unwrap_or(usize::MAX);
-    if start == usize::MAX {
-        return Err(String::from("invalid arg for format"));
-    }
-    let fstring = &s[start..];
-    let stop = fstring.find("f").unwrap_or(usize::MAX);
-    if stop == usize::MAX {
-        return Err(String::from("invalid arg for format"));
-    }
-    if fstring[1..stop].is_empty() {
-        return Ok(());
+pub fn validate_field(s: String) -> Result<(), String> {
+    s.parse::<FieldSelector>().map(|_| ())
+}
+
+pub fn validate_unit_size(s: String) -> Result<(), String> {
+    parse_unit_size(&s).map(|_| ())
+}
+
+pub fn parse_unit_size(s: &str) -> Result<u128, String> {
+    /*
+    Read a unit size such as 512, 1K (1000) or 4Ki (4096),
+    the suffix being read as with --from=auto. Sizes are integers,
+    kept exact even when a f64 cannot hold them, like 1Y.
+    */
+    let err = || format!("invalid unit size: '{}'", s);
+    let mut number = s.to_string();
+    let mut suffix = String::new();
+    strip_number(&mut number, &mut suffix).map_err(|_| err())?;
+    if !number.chars().all(|c| DIGITS.contains(c)) {
+        return Err(err());
     }
-    match fstring[1..stop].parse::<f64>() {
-        Ok(_) => Ok(()),
-        Err(_) => Err(String::from("invalid arg for format")),
+    let size = number.parse::<u128>().map_err(|_| err())?;
+    let factor = match SI_SUFFIXES.iter().position(|&x| x == suffix) {
+        Some(i) => checked_power(1000, i as u32),
+        None => match IEC_SUFFIXES.iter().position(|&x| x == suffix) {
+            Some(i) => checked_power(1024, i as u32),
+            None => return Err(err()),
+        },
+    };
+    match factor.and_then(|factor| size.checked_mul(factor)) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(err()),
     }
 }
 
+pub fn validate_format(s: String) -> Result<(), String> {
+    s.parse::<FormatSpec>().map(|_| ())
+}
+
 pub fn validate_unit_from(s: String) -> Result<(), String> {
-    match s.to_lowercase().as_str() {
-        "auto" => Ok(()),
-        "si" => Ok(()),
-        "iec" => Ok(()),
-        "iec-i" => Ok(()),
-        _ => Err(String::from("invalid unit arg")),
-    }
+    s.parse::<Unit>().map(|_| ())
 }
 
 pub fn validate_unit_to(s: String) -> Result<(), String> {
-    match s.to_lowercase().as_str() {
-        "si" => Ok(()),
-        "iec" => Ok(()),
-        "iec-i" => Ok(()),
-        _ => Err(String::from("invalid unit arg")),
-    }
+    s.parse::<Unit>().map(|_| ())
 }
 
 pub fn validate_invalid(s: String) -> Result<(), String> {
-    match s.to_lowercase().as_str() {
-        "fail" => Ok(()),
-        "warn" => Ok(()),
-        "ignore" => Ok(()),
-        "abort" => Ok(()),
-        _ => Err(String::from("invalid invalid mode")),
-    }
+    s.parse::<InvalidMode>().map(|_| ())
 }
 
 pub fn validate_round(s: String) -> Result<(), String> {
-    match s.to_lowercase().as_str() {
-        "up" => Ok(()),
-        "down" => Ok(()),
-        "from-zero" => Ok(()),
-        "towards-zero" => Ok(()),
-        "nearest" => Ok(()),
-        _ => Err(String::from("invalid round method")),
-    }
+    s.parse::<RoundMethod>().map(|_| ())
+}
+
+pub fn validate_suffix_case(s: String) -> Result<(), String> {
+    s.parse::<SuffixCase>().map(|_| ())
+}
+
+pub fn validate_base_unit(s: String) -> Result<(), String> {
+    s.parse::<BaseUnit>().map(|_| ())
 }
 
-pub fn validate_si_suffix(s: &String) -> bool {
-    SI_SUFFIXES.contains(&(s.as_str()))
+pub fn validate_si_suffix(s: &str) -> bool {
+    SI_SUFFIXES.contains(&s)
 }
 
-pub fn validate_ieci_suffix(s: &String) -> bool {
-    IEC_SUFFIXES.contains(&(s.as_str()))
+pub fn validate_ieci_suffix(s: &str) -> bool {
+    IEC_SUFFIXES.contains(&s)
 }
 
-pub fn get_si_power(base: &mut u32, power: &mut u32, number: &mut f64, unit: &String) {
+pub fn validate_sub_unit_suffix(s: &str) -> bool {
+    SUB_UNIT_SUFFIXES.contains(&s)
+}
+
+pub fn validate_extended_suffix(s: &str) -> bool {
+    EXTENDED_SI_SUFFIXES.contains(&s) || EXTENDED_IEC_SUFFIXES.contains(&s)
+}
+
+pub fn normalize_suffix_case(unit: Unit, s: &str) -> Option<String> {
+    /*
+    Spell a suffix written in any case the way the suffix tables do,
+    like k to K or KI to Ki. None when the suffix is ambiguous: with the
+    sub-units of --from=si and auto, m and p could be milli and pico
+    as well as mega and peta.
+    */
+    let sub_units = matches!(unit, Unit::Si | Unit::Auto);
+    if sub_units && (s == "m" || s == "p") {
+        return None;
+    }
+    let known = |x: &str| {
+        validate_si_suffix(x)
+            || validate_ieci_suffix(x)
+            || (sub_units && validate_sub_unit_suffix(x))
+            || validate_extended_suffix(x)
+    };
+    if known(s) {
+        return Some(s.to_string());
+    }
+    let upper = s.to_uppercase();
+    let candidates = [upper.clone(), s.to_lowercase(), upper.replace('I', "i")];
+    Some(
+        candidates
+            .iter()
+            .find(|x| known(x))
+            .cloned()
+            .unwrap_or_else(|| s.to_string()),
+    )
+}
+
+pub fn split_base_unit(suffix: &str) -> (&str, &str) {
+    /*
+    Split a suffix such as GB, Mbit/s or " MiB" into the prefix scaling
+    the number and the base unit, which is kept as written.
+    Blanks are allowed between the number and a prefixed base unit.
+    */
+    let rate = suffix.strip_suffix("/s");
+    let body = rate.unwrap_or(suffix);
+    let prefix_len = match BASE_UNITS.iter().find(|unit| body.ends_with(*unit)) {
+        Some(unit) => body.len() - unit.len(),
+        None if rate.is_some() => body.len(),
+        None => return (suffix, ""),
+    };
+    let (prefix, unit) = suffix.split_at(prefix_len);
+    (prefix.trim_start_matches(' '), unit)
+}
+
+pub fn get_si_power(base: &mut u32, power: &mut i32, number: &mut f64, unit: &str) {
     *base = 10;
-    *power = match unit.as_str() {
-        "K" => (3),
-        "M" => (6),
-        "G" => (9),
-        "T" => (12),
-        "P" => (15),
-        "E" => (18),
-        "Z" => (21),
-        "Y" => (24),
-        _ => (0),
+    *power = match unit {
+        "K" => 3,
+        "M" => 6,
+        "G" => 9,
+        "T" => 12,
+        "P" => 15,
+        "E" => 18,
+        "Z" => 21,
+        "Y" => 24,
+        "R" => 27,
+        "Q" => 30,
+        "m" => -3,
+        "u" | "µ" => -6,
+        "n" => -9,
+        "p" => -12,
+        "f" => -15,
+        _ => 0,
     };
-    let numb_power = number.log10().trunc() as u32;
-    *power += 3*(numb_power/3);
-    *number /= (*base).pow(3*(numb_power/3)) as f64;
+    // the sign does not change the scale
+    let numb_power = 3 * (number.abs().log10().trunc() as u32 / 3) as i32;
+    *power += numb_power;
+    *number /= f64::from(*base).powi(numb_power);
 }
 
-pub fn get_iec_power(base: &mut u32, power: &mut u32, number: &mut f64, unit: &String) {
+pub fn get_iec_power(base: &mut u32, power: &mut i32, number: &mut f64, unit: &str) {
     *base = 2;
-    *power = match unit.as_str() {
-        "K" | "Ki" => (10),
-        "M" | "Mi" => (20),
-        "G" | "Gi" => (30),
-        "T" | "Ti" => (40),
-        "P" | "Pi" => (50),
-        "E" | "Ei" => (60),
-        "Z" | "Zi" => (70),
-        "Y" | "Yi" => (80),
-        _ => (0),
+    *power = match unit {
+        "K" | "Ki" => 10,
+        "M" | "Mi" => 20,
+        "G" | "Gi" => 30,
+        "T" | "Ti" => 40,
+        "P" | "Pi" => 50,
+        "E" | "Ei" => 60,
+        "Z" | "Zi" => 70,
+        "Y" | "Yi" => 80,
+        "R" | "Ri" => 90,
+        "Q" | "Qi" => 100,
+        _ => 0,
     };
-    let numb_power = number.log2().trunc() as u32;
-    *power += 10*(numb_power/10);
-    *number /= (*base).pow(10*(numb_power/10)) as f64;
+    let numb_power = 10 * (number.abs().log2().trunc() as u32 / 10) as i32;
+    *power += numb_power;
+    *number /= f64::from(*base).powi(numb_power);
 }
 
-pub fn get_auto_power(base: &mut u32, power: &mut u32, number: &mut f64, s: &String) {
-    if validate_si_suffix(s) {
+pub fn get_auto_power(base: &mut u32, power: &mut i32, number: &mut f64, s: &str) {
+    if validate_si_suffix(s) || validate_sub_unit_suffix(s) || EXTENDED_SI_SUFFIXES.contains(&s) {
         get_si_power(base, power, number, s);
         return;
     }
-    if validate_ieci_suffix(s) {
+    if validate_ieci_suffix(s) || EXTENDED_IEC_SUFFIXES.contains(&s) {
         get_iec_power(base, power, number, s);
-        return;
     }
 }
 
-pub fn to_si_power(base: &u32, power: &mut u32) -> String {
+pub fn get_suffix_power(from: Unit, suffix: &str) -> (u32, i32) {
+    /*
+    Base and power of an input suffix alone, without normalizing any number.
+    */
+    let (mut base, mut power, mut one) = (10, 0, 1.0);
+    match from {
+        Unit::Si => get_si_power(&mut base, &mut power, &mut one, suffix),
+        Unit::Iec | Unit::IecI => get_iec_power(&mut base, &mut power, &mut one, suffix),
+        Unit::Auto => get_auto_power(&mut base, &mut power, &mut one, suffix),
+        Unit::None | Unit::K8s => (),
+    };
+    (base, power)
+}
+
+pub fn checked_power(base: u32, power: u32) -> Option<u128> {
+    /*
+    Scaling factor of a suffix, u128 holds every SI and IEC factor up to Q and Qi.
+    */
+    u128::from(base).checked_pow(power)
+}
+
+pub fn get_exact_value(number: &str, base: u32, power: i32, unit_size: u128) -> Option<u128> {
+    /*
+    Exact integer value of a number written in decimal with a suffix and
+    an input unit size, None when it has a fractional part or does not fit.
+    The sign is left to the caller, only the magnitude is returned.
+    */
+    let number = number.trim_start_matches(['+', '-']);
+    let (mantissa, exponent) = split_exponent(number);
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac).parse::<u128>().ok()?;
+    let mut divisor = 10_u128.checked_pow(frac.len() as u32)?;
+    let mut value = digits.checked_mul(unit_size)?;
+    if exponent < 0 {
+        divisor = divisor.checked_mul(10_u128.checked_pow(exponent.unsigned_abs())?)?;
+    } else {
+        value = value.checked_mul(10_u128.checked_pow(exponent as u32)?)?;
+    }
+    if power < 0 {
+        divisor = divisor.checked_mul(checked_power(base, power.unsigned_abs())?)?;
+    } else {
+        value = value.checked_mul(checked_power(base, power as u32)?)?;
+    }
+    match value % divisor {
+        0 => Some(value / divisor),
+        _ => None,
+    }
+}
+
+pub fn to_si_power(base: &u32, power: &mut i32) -> String {
     if *base == 2 {
         // 2**(10*x) == 10**(3*x) for IEC standarts
         // base_2 power <=> base_10 power*3/10
         *power = (*power / 10) * 3;
     }
     match *power {
+        p if p >= 30 => "Q".to_string(),
+        p if p >= 27 => "R".to_string(),
         p if p >= 24 => {
             //*power -= 24;
             "Y".to_string()
@@ -208,11 +343,16 @@ pub fn to_si_power(base: &u32, power: &mut u32) -> String {
             //*power -= 3;
             "K".to_string()
         }
-        _ => "".to_string(),
+        p if p >= 0 => "".to_string(),
+        p if p >= -3 => "m".to_string(),
+        p if p >= -6 => "u".to_string(),
+        p if p >= -9 => "n".to_string(),
+        p if p >= -12 => "p".to_string(),
+        _ => "f".to_string(),
     }
 }
 
-pub fn to_iec_power(iec_i: bool, base: &u32, power: &mut u32) -> String {
+pub fn to_iec_power(iec_i: bool, base: &u32, power: &mut i32) -> String {
     let i = match iec_i {
         true => "i",
         false => "",
@@ -223,37 +363,39 @@ pub fn to_iec_power(iec_i: bool, base: &u32, power: &mut u32) -> String {
         *power = (*power * 10) / 3;
     }
     match *power {
+        p if p >= 100 => "Q".to_string() + i,
+        p if p >= 90 => "R".to_string() + i,
         p if p >= 80 => {
             //*power -= 80;
-            "Y".to_string() + &i
+            "Y".to_string() + i
         }
         p if p >= 70 => {
             //*power -= 70;
-            "Z".to_string() + &i
+            "Z".to_string() + i
         }
         p if p >= 60 => {
             //*power -= 60;
-            "E".to_string() + &i
+            "E".to_string() + i
         }
         p if p >= 50 => {
             //*power -= 50;
-            "P".to_string() + &i
+            "P".to_string() + i
         }
         p if p >= 40 => {
             //*power -= 40;
-            "T".to_string() + &i
+            "T".to_string() + i
         }
         p if p >= 30 => {
             //*power -= 30;
-            "G".to_string() + &i
+            "G".to_string() + i
         }
         p if p >= 20 => {
             //*power -= 20;
-            "M".to_string() + &i
+            "M".to_string() + i
         }
         p if p >= 10 => {
             //*power -= 10;
-            "K".to_string() + &i
+            "K".to_string() + i
         }
         _ => "".to_string(),
     }
@@ -264,57 +406,44 @@ pub fn change_system(from_base: &u32, to_base: &u32, power: &u32, number: &mut f
         2 => (*power / 10) * 3,
         _ => (*power * 10) / 3,
     };
-    *number *= (*from_base).pow(*power) as f64 / (*to_base).pow(corresponding_power) as f64;
+    *number *= f64::from(*from_base).powi(*power as i32)
+        / f64::from(*to_base).powi(corresponding_power as i32);
 }
 
 pub fn get_fields(fields: String) -> (usize, usize) {
-    match fields.find("-") {
-        Some(_i) => {
-            let tmp = fields.split_once("-").unwrap();
-            (
-                tmp.0.parse::<usize>().unwrap_or(usize::MAX),
-                tmp.1.parse::<usize>().unwrap_or(usize::MAX),
-            )
-        }
-        _ => {
-            match fields.parse::<usize>(){
-                Ok(i) => (i,i),
-                Err(_) => (1, usize::MAX)
-            }
+    match fields.split_once('-') {
+        Some(tmp) => (
+            tmp.0.parse::<usize>().unwrap_or(usize::MAX),
+            tmp.1.parse::<usize>().unwrap_or(usize::MAX),
+        ),
+        _ => match fields.parse::<usize>() {
+            Ok(i) => (i, i),
+            Err(_) => (1, usize::MAX),
         },
     }
 }
 
-pub fn padding(res: &String, res_unit: &String, suffix: &String, n_padding: i64) -> String {
-    let length = n_padding as usize - min(res.len() + res_unit.len(), n_padding as usize);
+pub fn padding(res: &str, res_unit: &str, suffix: &str, n_padding: i64) -> String {
+    let width = n_padding.unsigned_abs() as usize;
+    // widths count characters, like the no-break spaces grouping digits in fr_FR
+    let length = width - min(res.chars().count() + res_unit.chars().count(), width);
     match n_padding {
         i if i >= 0 => {
             let padding = " ".repeat(length);
-            format!("{}{}{}{}", padding, *res, *res_unit, *suffix)
+            format!("{}{}{}{}", padding, res, res_unit, suffix)
         }
         _ => {
             let padding = " ".repeat(length);
-            format!("{}{}{}{}", *res, *res_unit, padding, *suffix)
+            format!("{}{}{}{}", res, res_unit, padding, suffix)
         }
     }
 }
 
-pub fn formatting(res: &String, res_unit: &String, suffix: &String, formatting: String) -> String {
-    let start = formatting.find("%").unwrap();
-    let (before, rest) = formatting.split_at(start);
-    let end = rest.find("f").unwrap();
-    let (format_core, after) = formatting.split_at(end + 1);
-    let res = padding(
-        &res,
-        &res_unit,
-        &suffix,
-        format_core
-            .trim_start_matches("%")
-            .trim_end_matches("f")
-            .parse::<i64>()
-            .unwrap_or(1),
-    );
-    format!("{}{}{}", before, res, after)
+pub fn formatting(res: &str, res_unit: &str, suffix: &str, formatting: String) -> String {
+    match formatting.parse::<FormatSpec>() {
+        Ok(spec) => spec.render(res, res_unit, suffix, 1),
+        Err(_) => padding(res, res_unit, suffix, 1),
+    }
 }
 
 pub fn strip_number(
@@ -322,247 +451,57 @@ pub fn strip_number(
     suffix: &mut String,
 ) -> Result<f64, std::num::ParseFloatError> {
     /*
-    Try striping the number by itering on chars until it is not a digit anymore.
+    Try striping the number by itering on chars until it is not a digit anymore,
+    after an optional sign. An exponent such as e6 or E-3 is part of the number
+    when it has digits, so 1E is still read with the E suffix.
     */
-    let mut char_indices = number.char_indices();
-    let mut char_index = char_indices.next();
-    loop {
-        match char_index {
-            Some((i, c)) => {
-                if !DIGITALS.contains(c) {
-                    let tmp = (*number).split_at(i);
-                    *suffix = tmp.1.to_string();
-                    *number = tmp.0.to_string();
-                    break;
-                }
-                char_index = char_indices.next();
-            }
-            None => {
-                *suffix = "".to_string();
-                break;
-            }
+    let start = if number.starts_with(['+', '-']) { 1 } else { 0 };
+    let mut end = number[start..]
+        .find(|c| !DIGITALS.contains(c))
+        .map_or(number.len(), |i| start + i);
+    if let Some(exponent) = number[end..].strip_prefix(['e', 'E']) {
+        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let len = digits.find(|c| !DIGITS.contains(c)).unwrap_or(digits.len());
+        if len > 0 {
+            end += number[end..].len() - digits.len() + len;
         }
     }
-    return (*number).parse::<f64>();
+    *suffix = number.split_off(end);
+    (*number).parse::<f64>()
 }
 
-pub fn numfmt_core(
-    mut number: String,
-    inputs: &ArgMatches,
-    locale_decimal_point: &str,
-    mut writer: impl std::io::Write,
-) -> Result<String, Box<dyn Error>> {
-
-    /* 
-    First part of this function about "understanding" or "importing" the number.
-    The second half is more about "preparing" or "exporting" the number.
+pub fn split_exponent(number: &str) -> (&str, i32) {
+    /*
+    Split a number read by strip_number into its mantissa and exponent,
+    huge exponents saturate as the number is then zero or infinite anyway.
     */
-
-    let debug = inputs.is_present("debug");
-    let mut base: u32 = 10;
-    let mut power: u32 = 1;
-    let mut suffix = String::new();
-
-    // convert string to number
-    let mut res: f64;
-    number = number.replace(locale_decimal_point, ".");
-    //println!("number {}", number);
-
-    match strip_number(&mut number, &mut suffix) {
-        Ok(n) => {
-            res = n;
-        }
-        Err(e) => {
-            return Err(Box::new(e));
+    match number.find(['e', 'E']) {
+        Some(i) => {
+            let exponent = &number[i + 1..];
+            let saturated = if exponent.starts_with('-') { i32::MIN } else { i32::MAX };
+            (&number[..i], exponent.parse().unwrap_or(saturated))
         }
+        None => (number, 0),
     }
-    //println!("res {}", res);
-
-    // determine base and power of number
-    let from = inputs.value_of("from").unwrap_or("auto");
-    match from.to_lowercase().as_str() {
-        "si" => get_si_power(&mut base, &mut power, &mut res, &suffix),
-        "iec" => get_iec_power(&mut base, &mut power, &mut res, &suffix),
-        "iec-i" => get_iec_power(&mut base, &mut power, &mut res, &suffix),
-        _ => get_auto_power(&mut base, &mut power, &mut res, &suffix),
-    };
-    
-    if debug {
-        writeln!(writer, "base:{:?}\npower:{:?}", base, power)?;
-    }
-    
-    // scale to unit_size
-    let unit_size = inputs
-        .value_of("to-unit")
-        .unwrap_or("1.0")
-        .parse::<f64>()
-        .unwrap();
-    res = res / unit_size;
-
-    if inputs.is_present("rounding") {
-        match inputs
-            .value_of("rouding")
-            .unwrap_or("from-zero")
-            .to_lowercase()
-            .as_str()
-        {
-            "up" => {
-                res = res.ceil();
-            }
-            "down" => {
-                res = res.floor();
-            }
-            "from-zero" => {
-                //away from-zero
-                res = res.trunc() + res.signum();
-            }
-            "towards-zero" => {
-                res = res.trunc();
-            }
-            "nearest" => {
-                res = res.round();
-            }
-            _ => {}
-        }
-    }
-
-    // preparing the base to export the number
-    let to_base = match inputs.value_of("to").unwrap_or("si") {
-        "iec" | "iec-i" => (2),
-        "si" | _ => (10),
-    };
-    // change base only if necessary
-    if base != to_base {
-        change_system(&base, &to_base, &power, &mut res);
-    }
-
-    if res.fract().to_string().len() > 5 {
-        // limiting fraction part
-        res = res.trunc() + (10.0*res.fract()).round()/10.0;
-    }
-
-    let mut res_unit = "".to_string();
-    if inputs.is_present("to") {
-        let to = inputs.value_of("to").unwrap();
-        res_unit = match to.to_lowercase().as_str() {
-            "si" => to_si_power(&base, &mut power),
-            "iec" => to_iec_power(false, &base, &mut power),
-            "iec-i" => to_iec_power(true, &base, &mut power),
-            _ => res_unit,
-        };
-    }
-    else{
-        res_unit = suffix;
-    }
-
-    let mut res = res.to_string();
-    // convert to exporting format
-    if inputs.is_present("grouping") {
-        let res_str = res.clone();
-        let (mut to_add, mut remain) = res_str.split_at(res_str.len().modulo(3));
-        let mut res_vec = vec![to_add];
-        while remain.len() > 3 {
-            let x = remain.split_at(3);
-            to_add = x.0;
-            remain = x.1;
-            res_vec.push(to_add.clone());
-        }
-        res_vec.push(remain.clone());
-        res = res_vec.join(",");
-    }
-    let suffix = inputs.value_of("suffix").unwrap_or("").to_string();
-    if inputs.is_present("format") {}
-
-    // format has higher priority because it include padding functionnalities
-    let to_print = match inputs.is_present("format") {
-        true => formatting(
-            &res,
-            &res_unit,
-            &suffix,
-            inputs.value_of("format").unwrap_or("%0f").to_string(),
-        ),
-        _ => padding(
-            &res,
-            &res_unit,
-            &suffix,
-            inputs
-                .value_of("padding")
-                .unwrap_or("1")
-                .parse::<i64>()
-                .unwrap(),
-        ),
-    };
+}
 
-    if debug {
-        println!("FINAL: {:?}{:?} {}", res, res_unit, suffix);
-        println!("or:{}",to_print);
-    }
-    Ok(to_print)
+pub fn numfmt_core(
+    number: String,
+    options: &NumfmtOptions,
+    locale_decimal_point: &str,
+) -> Result<String, NumfmtError> {
+    Formatter::new(options, locale_decimal_point)?.convert(&number)
 }
 
+/// Convert the selected fields of one line and write it to `writer`.
+///
+/// This builds a new `Formatter` on each call, use `Formatter::process_line`
+/// directly when converting many lines.
 pub fn numfmt(
     line: String,
-    inputs: &ArgMatches,
+    options: &NumfmtOptions,
     locale_decimal_point: &str,
     mut writer: impl std::io::Write,
-) -> Result<(), Box<dyn Error>> {
-    let delimiter = inputs.value_of("delimiter").unwrap_or(" ");
-    let invalid_mode = inputs.value_of("invalid").unwrap_or("fail"); //default is abort
-    let (mut start, end) = get_fields(inputs.value_of("field").unwrap_or(&DEFAULT_FIELD).to_string());
-    if start == usize::MAX {
-        start = 1;
-    }
-    //println!("fields {},{}", start, end);
-
-    let re = Regex::new(
-        &(format!(r"([^{}]+|[{}]+)", delimiter, delimiter).as_str())
-    ).unwrap();
-
-    let mut index = 0;
-    let mut space: bool;
-    for cap in re.captures_iter(&line) {
-        // iter on each group of chars
-        let field = &cap[0];
-        space = field.contains(delimiter);
-        if !space{
-            index += 1;
-        }
-        //println!("re line: {} at {}", field, index);
-        if !space && start <= index && index <= end{
-            // if the group of char is one of those asked by the user, format it
-            match invalid_mode {
-                "fail" => {
-                    match numfmt_core(field.to_string(), &inputs, &locale_decimal_point, &mut writer) {
-                        Ok(res) => write!(writer, "{}", res)?,
-                        Err(err_string) => {
-                            return Err(err_string);
-                        }
-                    };
-                }
-                "warn" => {
-                    match numfmt_core(field.to_string(), &inputs, &locale_decimal_point, &mut writer) {
-                        Ok(res) => write!(writer, "{}", res)?,
-                        Err(err_string) => write!(writer, "{}", err_string)?,
-                    };
-                }
-                "ignore" => {
-                    match numfmt_core(field.to_string(), &inputs, &locale_decimal_point, &mut writer) {
-                        Ok(res) => write!(writer, "{}", res)?,
-                        Err(_) => (),
-                    };
-                }
-                "abort" | _ => {
-                    match numfmt_core(field.to_string(), &inputs, &locale_decimal_point, &mut writer) {
-                        Ok(res) => write!(writer, "{}", res)?,
-                        Err(_) => break,
-                    };
-                }
-            };
-        }
-        else{
-            write!(writer, "{}", field)?;
-        }
-    }
-    // write newline
-    Ok(write!(writer, "{}", if inputs.is_present("zero_terminated") {"\0"} else {"\n"})?)
+) -> Result<Vec<NumfmtError>, NumfmtError> {
+    Formatter::new(options, locale_decimal_point)?.process_line(&line, &mut writer)
 }
@@ -0,0 +1,150 @@
+use std::env;
+
+/// Decimal point, thousands separator and digit groups of the locales numfmt knows,
+/// others fall back on their language or on the C locale.
+const LOCALES: [(&str, &str, &str, &[usize]); 18] = [
+    ("C", ".", "", &[]),
+    ("POSIX", ".", "", &[]),
+    ("en_US", ".", ",", &[3]),
+    ("en_GB", ".", ",", &[3]),
+    ("en_IN", ".", ",", &[3, 2]),
+    ("hi_IN", ".", ",", &[3, 2]),
+    ("de_DE", ",", ".", &[3]),
+    ("es_ES", ",", ".", &[3]),
+    ("it_IT", ",", ".", &[3]),
+    ("nl_NL", ",", ".", &[3]),
+    ("pt_BR", ",", ".", &[3]),
+    ("fr_FR", ",", "\u{202f}", &[3]),
+    ("pl_PL", ",", "\u{a0}", &[3]),
+    ("ru_RU", ",", "\u{202f}", &[3]),
+    ("sv_SE", ",", "\u{a0}", &[3]),
+    ("ja_JP", ".", ",", &[3]),
+    ("ko_KR", ".", ",", &[3]),
+    ("zh_CN", ".", ",", &[3]),
+];
+
+/// Numeric conventions of a locale, as `LC_NUMERIC` defines them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    pub decimal_point: String,
+    /// Empty when digits are not grouped, like in the C locale.
+    pub thousands_sep: String,
+    /// Sizes of the digit groups from the decimal point, the last one repeating,
+    /// like `[3, 2]` for the lakh and crore of `hi_IN`.
+    pub grouping: Vec<usize>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::c()
+    }
+}
+
+impl Locale {
+    /// The C and POSIX locale, where digits are not grouped.
+    pub fn c() -> Self {
+        Locale {
+            decimal_point: ".".to_string(),
+            thousands_sep: String::new(),
+            grouping: Vec::new(),
+        }
+    }
+
+    /// Look a locale up by name, like `fr_FR.UTF-8` or `de_DE@euro`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        // the codeset and modifier do not change the numeric conventions
+        let name = name.split(['.', '@']).next().unwrap_or(name);
+        let language = name.split('_').next().unwrap_or(name);
+        let found = LOCALES.iter().find(|entry| entry.0 == name).or_else(|| {
+            LOCALES
+                .iter()
+                .find(|entry| entry.0.split('_').next() == Some(language))
+        });
+        found.map(|&(_, decimal_point, thousands_sep, grouping)| Locale {
+            decimal_point: decimal_point.to_string(),
+            thousands_sep: thousands_sep.to_string(),
+            grouping: grouping.to_vec(),
+        })
+    }
+
+    /// Group the digits of an integer, like `1234567` to `1,234,567`
+    /// or to `12,34,567` with the `[3, 2]` groups of `hi_IN`.
+    pub fn group_digits(&self, digits: &str) -> String {
+        let mut groups = Vec::new();
+        let mut end = digits.len();
+        let mut sizes = self.grouping.iter();
+        let mut size = 0;
+        while end > 0 {
+            // the last size is repeated, and 0 stops grouping
+            size = sizes.next().copied().unwrap_or(size);
+            if size == 0 {
+                break;
+            }
+            let start = end.saturating_sub(size);
+            groups.push(&digits[start..end]);
+            end = start;
+        }
+        if end > 0 {
+            groups.push(&digits[..end]);
+        }
+        groups.reverse();
+        groups.join(&self.thousands_sep)
+    }
+
+    /// Remove the thousands separators of a grouped number like `1,234,567.5K`,
+    /// or `None` when they are not where the locale puts them, like in `12,34,5`.
+    ///
+    /// A blank is accepted for the no-break spaces of locales such as `fr_FR`.
+    pub fn ungroup(&self, number: &str) -> Option<String> {
+        let mut separators = vec![self.thousands_sep.as_str()];
+        if self.thousands_sep.chars().all(char::is_whitespace) {
+            separators.push(" ");
+        }
+        let start = if number.starts_with(['+', '-']) { 1 } else { 0 };
+        let mut groups = Vec::new();
+        let mut rest = &number[start..];
+        loop {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            groups.push(&rest[..len]);
+            rest = &rest[len..];
+            // a separator is only part of the number when digits follow it
+            let next = separators.iter().filter(|sep| !sep.is_empty()).find(|sep| {
+                rest.strip_prefix(**sep)
+                    .is_some_and(|tail| tail.starts_with(|c: char| c.is_ascii_digit()))
+            });
+            match next {
+                Some(sep) => rest = &rest[sep.len()..],
+                None => break,
+            }
+        }
+        if groups.len() == 1 {
+            return Some(number.to_string());
+        }
+
+        let last = *self.grouping.last()?;
+        for (i, group) in groups.iter().rev().enumerate() {
+            let size = self.grouping.get(i).copied().unwrap_or(last);
+            // the leftmost group can be shorter
+            let valid = if i + 1 == groups.len() {
+                (1..=size).contains(&group.len())
+            } else {
+                group.len() == size
+            };
+            if !valid {
+                return None;
+            }
+        }
+        Some(format!("{}{}{}", &number[..start], groups.concat(), rest))
+    }
+
+    /// The locale selected by `LC_ALL`, `LC_NUMERIC` or `LANG`, in that order,
+    /// unknown and unset locales being read as the C locale.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|name| !name.is_empty())
+            .and_then(|name| Locale::from_name(&name))
+            .unwrap_or_default()
+    }
+}
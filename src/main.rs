@@ -1,8 +1,6 @@
 use clap::{App, Arg};
-use exitcode;
-use std::cmp::{min, max};
+use numfmt::{Formatter, InvalidMode, Locale, NumfmtError, NumfmtOptions, EXIT_CONVERSION_FAILURE};
 use std::io::{self, BufRead, Write};
-use std::process::Command;
 
 
 fn main() {
@@ -10,6 +8,9 @@ fn main() {
     	.version("0.1")
     	.author("mifour")
     	.about("rewrite of numfmt in rust - Convert numbers from/to human-readable strings")
+    	.arg(Arg::with_name("accept-grouping")
+           .long("accept-grouping")
+           .help("accept input numbers grouped with the locale thousands separator, like 1,234,567, rejecting misplaced separators"))
     	.arg(
     		Arg::with_name("debug")
     		.long("debug")
@@ -20,6 +21,9 @@ fn main() {
            .value_name("DELIMITER")
            .help("change delimiter from whitespace to X")
            .takes_value(true))
+    	.arg(Arg::with_name("extended-prefixes")
+           .long("extended-prefixes")
+           .help("accept and print the R (10^27) and Q (10^30) prefixes, unknown to GNU numfmt"))
     	.arg(Arg::with_name("field")
            .short("f")
            .long("field")
@@ -39,9 +43,20 @@ fn main() {
            .help("specify the input unit size (default 1")
            .validator(numfmt::validate_unit_from)
            .takes_value(true))
+    	.arg(Arg::with_name("from-unit")
+           .long("from-unit")
+           .value_name("N")
+           .validator(numfmt::validate_unit_size)
+           .help("specify the input unit size, like 512 or 1Ki (default 1)")
+           .takes_value(true))
     	.arg(Arg::with_name("grouping")
            .long("grouping")
            .help("use locale defined grouping of digits e.g. 1,000,000 (which means it has no effect on the C/POSIX locale)"))
+    	.arg(Arg::with_name("grouping-separator")
+           .long("grouping-separator")
+           .value_name("SEPARATOR")
+           .help("with --grouping, separate digit groups with SEPARATOR instead of the locale separator, grouping by threes in the C/POSIX locale")
+           .takes_value(true))
     	.arg(Arg::with_name("header") // ToDo: fix with 0 explicit args
            .long("header")
            .value_name("N")
@@ -60,28 +75,49 @@ fn main() {
            .help("pad the output to N characters; positive N will right-align, negative N will left-align; padding is ignored if the output is wider than N, the default is to automatically pad if a whitespace is found.")
            .validator(numfmt::is_int)
            .takes_value(true))
+    	.arg(Arg::with_name("precision")
+           .long("precision")
+           .value_name("N")
+           .help("print N decimals, a %.Nf precision in --format takes priority (default: one decimal for scaled values below 10)")
+           .validator(numfmt::positive_int)
+           .takes_value(true))
     	.arg(Arg::with_name("round")
            .long("round")
            .value_name("METHOD")
            .help("ues METHOD for rounding when scaling among up, down, from-zero (default), towards-zero, nearest")
            .validator(numfmt::validate_round)
            .takes_value(true))
+    	.arg(Arg::with_name("sub-units")
+           .long("sub-units")
+           .help("with --to=si, print values below 1 with the m, u, n, p and f prefixes"))
     	.arg(Arg::with_name("suffix")
            .long("suffix")
            .value_name("SUFFIX")
            .help("add SUFFIX to ouput numbers, and accept optionnal SUFFIX in input numbers")
            .takes_value(true))
+    	.arg(Arg::with_name("suffix-case")
+           .long("suffix-case")
+           .value_name("CASE")
+           .help("match input suffixes among: strict (default), insensitive; with insensitive, m and p are ambiguous")
+           .validator(numfmt::validate_suffix_case)
+           .takes_value(true))
     	.arg(Arg::with_name("to")
            .long("to")
            .value_name("UNIT")
-           .help("auto scale output to UNITs (see UNITs)")
+           .help("auto scale output to UNITs (see UNITs), numbers are written unscaled without it")
            .validator(numfmt::validate_unit_to)
            .takes_value(true))
+    	.arg(Arg::with_name("to-base-unit")
+           .long("to-base-unit")
+           .value_name("UNIT")
+           .help("convert numbers written in bits or bytes (1Gbit, 125MB, 10Gbps) to UNIT among: bits, bytes")
+           .validator(numfmt::validate_base_unit)
+           .takes_value(true))
     	.arg(Arg::with_name("to-unit")
            .long("to-unit")
-           .value_name("UNIT_SIZE")
-           .validator(numfmt::strick_positive_int)
-           .help("the output unit size (default 1)")
+           .value_name("N")
+           .validator(numfmt::validate_unit_size)
+           .help("the output unit size, like 512 or 1Ki (default 1)")
            .takes_value(true))
     	.arg(Arg::with_name("zero_terminated")
            .short("z")
@@ -89,13 +125,20 @@ fn main() {
            .help("line delimiter is NUL, not new-line"))
     	.arg(
     		Arg::with_name("NUMBER")
-    		.help("input to use"))
+    		.multiple(true)
+    		.help("inputs to use, each one is converted on its own line"))
     	.after_help(
     		"UNIT options:\n\tnone   no auto-scaling is done; suffixes will trigger an error
-\tauto   accept optional single/two letter suffix(1K = 1000, 1Ki = 1024, 1M = 1000000, 1Mi = 1048576)
-\tsi     accept optional single letter suffix(1K = 1000, 1M = 1000000)
+\tauto   accept optional single/two letter suffix(1K = 1000, 1Ki = 1024, 1M = 1000000, 1Mi = 1048576, 1m = 0.001)
+\t       with --to, write each number in the system of its suffix(2048Ki -> 2.0Mi, 3000K -> 3.0M, 1500 -> 1500)
+\tsi     accept optional single letter suffix(1K = 1000, 1M = 1000000, 1m = 0.001)
 \tiec    accept optional single letter suffix(1K = 1024, 1M = 1048576)
 \tiec-i  accept optional two-letter suffix(1Ki = 1024, 1Mi = 1048576)
+\tk8s    Kubernetes quantities(512Mi, 250m, 1.5G, 1e3), written back in canonical form
+\tWith --extended-prefixes, R, Q, Ri and Qi are accepted and printed too.
+\tsi and auto also accept the m, u (or µ), n, p and f prefixes, printed with --to=si --sub-units.
+\tA base unit after the suffix (B, bytes, bit, b, bps, Hz, optionally /s) is kept in the output: 1.5GB, 10Mbit/s.
+\tWith --to-base-unit, bits and bytes are converted into each other: 10Gbps is 1.25GB/s.
 \nFIELDS supports cut(1) style field ranges:
 \tN      N'th field, counted from 1
 \tN-     from N'th field, to end of line
@@ -125,95 +168,105 @@ fn main() {
 \t$ ls -lh | numfmt --header --field 5 --from=iec --padding=10
 \t$ ls -lh | numfmt --header --field 5 --from=iec --format %10f")
     .get_matches();
+    let options = NumfmtOptions::from(&inputs);
 
+    // decimal point and digit grouping of LC_ALL, LC_NUMERIC or LANG
+    let locale = Locale::from_env();
 
-    let mut writer = io::stdout();
-
-    // Retrieve the main arg NUMBER from Clap if possible else,
-    // try with stdin (in case of pipe command)
-    let mut numbers = match inputs.value_of("NUMBER") {
-        Some(value) => String::from(value),
-        None => io::stdin()
-            .lock()
-            .lines()
-            .map(|line| line.unwrap())
-            .take_while(|line| !line.is_empty())
-            .collect::<Vec<String>>()
-            .join("\n"),
+    let formatter = match Formatter::with_locale(&options, &locale) {
+        Ok(formatter) => formatter,
+        Err(e) => {
+            eprintln!("numfmt: {}", e);
+            std::process::exit(e.exit_code());
+        }
     };
 
-    if numbers.is_empty() {
-        eprintln!("{}", "The <NUMBER> required arguments were not provided");
-        std::process::exit(exitcode::NOINPUT);
-    }
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    let mut exit_code = exitcode::OK;
 
-    // determine the local decimal point symbol
-    // TODO: fully implment locale LC_NUMERIC support
-    let locale_output = match Command::new("locale").arg("LC_NUMERIC").output() {
-        Ok(output) => (output.stdout),
-        _ => (vec![46 as u8]),
-    };
-    //println!("locale output {:?}", &locale_output[..1]);
-    let locale_decimal_point = match std::str::from_utf8(&locale_output[..1]) {
-        Ok(s) => match s{
-            "," => (","),
-            _ => (".")
-        },
-        Err(_) => ".", //default en_US.UTF-8
-    };
-    //println!("locale {}", locale_decimal_point);
-    
-    if inputs.is_present("zero_terminated") {
-        numbers = numbers.replace("\0", "\n");
-    }
-
-    //writing headers lines without parsing the content
-    let header = inputs
-        .value_of("header")
-        .unwrap_or("0")
-        .parse::<usize>()
-        .unwrap();
-    let mut header_end: usize = 0;
-    if header > 0 {
-        let indices: Vec<(usize, &str)> = numbers.match_indices("\n").collect();
-        header_end = indices[max(min(indices.len(), header) - 1, 0)].0;
-    }
-    let h_text = &numbers[..header_end];
-    if !h_text.is_empty(){
-        match writeln!(writer, "{}", h_text) {
-            Ok(_) => (),
-            Err(_) => std::process::exit(exitcode::IOERR),
-        };
-    }
-    numbers = numbers[header_end..].to_string();
-    
-    if numbers.starts_with("\n"){
-        let _ = numbers.remove(0);
-    }
-    
-    for number in numbers.lines() {
-        // iter line by line
-        //println!("line: {}", number);
-        match numfmt::numfmt(number.to_string(), &inputs, &locale_decimal_point, &mut writer) {
-            Ok(_) => (),
-            Err(e) => {
-                if let Some(err) = e.downcast_ref::<io::Error>() {
-                    eprintln!("IO Error: {}", err);
+    // Retrieve the main args NUMBER from Clap if possible else,
+    // stream stdin line by line (in case of pipe command)
+    if let Some(numbers) = inputs.values_of("NUMBER") {
+        for (i, number) in numbers.enumerate() {
+            convert_line(&formatter, number, i + 1, &mut writer, &mut exit_code);
+        }
+    } else {
+        let line_end = if options.zero_terminated { b'\0' } else { b'\n' };
+        let stdin = io::stdin();
+        let mut reader = stdin.lock();
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        loop {
+            buffer.clear();
+            match reader.read_until(line_end, &mut buffer) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(e) => {
+                    eprintln!("numfmt: IO Error: {}", e);
                     std::process::exit(exitcode::IOERR);
                 }
-                else if let Some(err) = e.downcast_ref::<std::string::ParseError>() {
-                    eprintln!("Parse Error: {}", err);
-                    std::process::exit(exitcode::DATAERR);
-                }
-                else{
-                    eprintln!("{}", e); 
-                    std::process::exit(exitcode::SOFTWARE);
+            };
+            line_number += 1;
+            if buffer.last() == Some(&line_end) {
+                buffer.pop();
+            }
+            let line = String::from_utf8_lossy(&buffer);
+
+            if line_number <= options.header {
+                //writing headers lines without parsing the content
+                let written = writer
+                    .write_all(line.as_bytes())
+                    .and_then(|_| writer.write_all(&[line_end]))
+                    .and_then(|_| writer.flush());
+                if written.is_err() {
+                    std::process::exit(exitcode::IOERR);
                 }
+            } else {
+                convert_line(&formatter, &line, line_number, &mut writer, &mut exit_code);
             }
-        };
+        }
+        if line_number == 0 {
+            eprintln!("The <NUMBER> required arguments were not provided");
+            std::process::exit(exitcode::NOINPUT);
+        }
     }
     let _ = writer.flush();
-    std::process::exit(exitcode::OK);
+    std::process::exit(exit_code);
+}
+
+fn convert_line(
+    formatter: &Formatter,
+    line: &str,
+    line_number: usize,
+    writer: &mut impl Write,
+    exit_code: &mut i32,
+) {
+    /*
+    Convert one line and flush it right away so numfmt can be used
+    in the middle of a pipeline, exiting on fatal errors.
+    */
+    match formatter.process_line(line, writer) {
+        Ok(warnings) => {
+            for warning in warnings {
+                // short lines are only reported with --debug, they are not failures
+                let failure = !matches!(warning, NumfmtError::FieldOutOfRange { .. });
+                eprintln!("numfmt: {}", warning.with_line(line_number));
+                if failure && formatter.options().invalid == InvalidMode::Fail {
+                    *exit_code = EXIT_CONVERSION_FAILURE;
+                }
+            }
+        }
+        Err(e) => {
+            let _ = writer.flush();
+            let e = e.with_line(line_number);
+            eprintln!("numfmt: {}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+    if writer.flush().is_err() {
+        std::process::exit(exitcode::IOERR);
+    }
 }
 
 //
\ No newline at end of file
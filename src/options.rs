@@ -0,0 +1,417 @@
+use std::str::FromStr;
+
+use clap::ArgMatches;
+
+use crate::{parse_unit_size, validate_field, validate_format, DEFAULT_FIELD};
+
+/// Unit system used to read (`--from`) or write (`--to`) numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// No suffix is accepted on input and no scaling is done on output.
+    None,
+    /// Any suffix with `--from`, and with `--to` the unit system of each input suffix.
+    Auto,
+    Si,
+    Iec,
+    IecI,
+    /// Kubernetes resource quantities, like `512Mi`, `250m` or `1e3`.
+    K8s,
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Unit::None),
+            "auto" => Ok(Unit::Auto),
+            "si" => Ok(Unit::Si),
+            "iec" => Ok(Unit::Iec),
+            "iec-i" => Ok(Unit::IecI),
+            "k8s" => Ok(Unit::K8s),
+            _ => Err(String::from("invalid unit arg")),
+        }
+    }
+}
+
+/// Rounding method selected with `--round`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundMethod {
+    Up,
+    Down,
+    FromZero,
+    TowardsZero,
+    Nearest,
+}
+
+impl FromStr for RoundMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "up" => Ok(RoundMethod::Up),
+            "down" => Ok(RoundMethod::Down),
+            "from-zero" => Ok(RoundMethod::FromZero),
+            "towards-zero" => Ok(RoundMethod::TowardsZero),
+            "nearest" => Ok(RoundMethod::Nearest),
+            _ => Err(String::from("invalid round method")),
+        }
+    }
+}
+
+impl RoundMethod {
+    /// Round `value` to `precision` decimals.
+    pub fn round(self, value: f64, precision: usize) -> f64 {
+        let factor = 10_f64.powi(precision as i32);
+        // every f64 is already exact with that many decimals
+        if !factor.is_finite() {
+            return value;
+        }
+        let mut scaled = value * factor;
+        // products such as 0.7 * 10 should stay exact values
+        let nearest = scaled.round();
+        if (scaled - nearest).abs() <= 16.0 * f64::EPSILON * nearest.abs().max(1.0) {
+            scaled = nearest;
+        }
+        let rounded = match self {
+            RoundMethod::Up => scaled.ceil(),
+            RoundMethod::Down => scaled.floor(),
+            RoundMethod::FromZero if scaled < 0.0 => scaled.floor(),
+            RoundMethod::FromZero => scaled.ceil(),
+            RoundMethod::TowardsZero => scaled.trunc(),
+            // halfway cases are rounded away from zero
+            RoundMethod::Nearest => scaled.round(),
+        };
+        // -0.4 rounded up is written 0, not -0
+        if rounded == 0.0 {
+            return 0.0;
+        }
+        rounded / factor
+    }
+}
+
+/// How input suffixes are matched, selected with `--suffix-case`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuffixCase {
+    /// Only the spelling of the tables, like GNU numfmt.
+    Strict,
+    /// Any case, like `1k` or `2KI`, but `m` and `p` are rejected as ambiguous.
+    Insensitive,
+}
+
+impl FromStr for SuffixCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(SuffixCase::Strict),
+            "insensitive" => Ok(SuffixCase::Insensitive),
+            _ => Err(String::from("invalid suffix case")),
+        }
+    }
+}
+
+/// Base unit written with `--to-base-unit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseUnit {
+    Bits,
+    Bytes,
+}
+
+impl FromStr for BaseUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bits" => Ok(BaseUnit::Bits),
+            "bytes" => Ok(BaseUnit::Bytes),
+            _ => Err(String::from("invalid base unit")),
+        }
+    }
+}
+
+/// Failure mode selected with `--invalid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidMode {
+    Abort,
+    Fail,
+    Warn,
+    Ignore,
+}
+
+impl FromStr for InvalidMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abort" => Ok(InvalidMode::Abort),
+            "fail" => Ok(InvalidMode::Fail),
+            "warn" => Ok(InvalidMode::Warn),
+            "ignore" => Ok(InvalidMode::Ignore),
+            _ => Err(String::from("invalid invalid mode")),
+        }
+    }
+}
+
+/// Every setting understood by `numfmt`, independent of the command line parser.
+///
+/// Build one with `NumfmtOptions::builder()` or convert it from clap's `ArgMatches`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumfmtOptions {
+    /// Read numbers whose digits are grouped, like `1,234,567`.
+    pub accept_grouping: bool,
+    pub debug: bool,
+    /// `None` means fields are separated by whitespace.
+    pub delimiter: Option<String>,
+    /// Accept and write the R and Q prefixes, which GNU numfmt does not know.
+    pub extended_prefixes: bool,
+    pub field: String,
+    pub format: Option<String>,
+    pub from: Unit,
+    /// Size of one input unit, input numbers are multiplied by it.
+    pub from_unit: u128,
+    pub grouping: bool,
+    /// Separator of the digit groups, instead of the one of the locale.
+    pub grouping_separator: Option<String>,
+    pub header: usize,
+    pub invalid: InvalidMode,
+    pub padding: Option<i64>,
+    /// `None` lets the precision depend on the output, see `Formatter`.
+    pub precision: Option<usize>,
+    pub round: RoundMethod,
+    /// Write values below 1 with the m, u, n, p and f prefixes when `to` is `Si`.
+    pub sub_units: bool,
+    pub suffix: Option<String>,
+    pub suffix_case: SuffixCase,
+    pub to: Unit,
+    /// Convert inputs written in bits or bytes to this base unit, before scaling.
+    pub to_base_unit: Option<BaseUnit>,
+    /// Size of one output unit, output numbers are divided by it.
+    pub to_unit: u128,
+    pub zero_terminated: bool,
+}
+
+impl Default for NumfmtOptions {
+    fn default() -> Self {
+        NumfmtOptions {
+            accept_grouping: false,
+            debug: false,
+            delimiter: None,
+            extended_prefixes: false,
+            field: DEFAULT_FIELD.to_string(),
+            format: None,
+            from: Unit::Auto,
+            from_unit: 1,
+            grouping: false,
+            grouping_separator: None,
+            header: 0,
+            invalid: InvalidMode::Abort,
+            padding: None,
+            precision: None,
+            round: RoundMethod::FromZero,
+            sub_units: false,
+            suffix: None,
+            suffix_case: SuffixCase::Strict,
+            to: Unit::None,
+            to_base_unit: None,
+            to_unit: 1,
+            zero_terminated: false,
+        }
+    }
+}
+
+impl NumfmtOptions {
+    pub fn builder() -> NumfmtOptionsBuilder {
+        NumfmtOptionsBuilder::default()
+    }
+}
+
+impl<'a> From<&ArgMatches<'a>> for NumfmtOptions {
+    /*
+    Values have already been checked by the clap validators,
+    so anything unparsable falls back to the default.
+    */
+    fn from(inputs: &ArgMatches<'a>) -> Self {
+        let default = NumfmtOptions::default();
+        NumfmtOptions {
+            accept_grouping: inputs.is_present("accept-grouping"),
+            debug: inputs.is_present("debug"),
+            delimiter: inputs.value_of("delimiter").map(String::from),
+            extended_prefixes: inputs.is_present("extended-prefixes"),
+            field: inputs
+                .value_of("field")
+                .map(String::from)
+                .unwrap_or(default.field),
+            format: inputs.value_of("format").map(String::from),
+            from: inputs
+                .value_of("from")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.from),
+            from_unit: inputs
+                .value_of("from-unit")
+                .and_then(|s| parse_unit_size(s).ok())
+                .unwrap_or(default.from_unit),
+            grouping: inputs.is_present("grouping"),
+            grouping_separator: inputs.value_of("grouping-separator").map(String::from),
+            header: inputs
+                .value_of("header")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.header),
+            invalid: inputs
+                .value_of("invalid")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.invalid),
+            padding: inputs.value_of("padding").and_then(|s| s.parse().ok()),
+            precision: inputs.value_of("precision").and_then(|s| s.parse().ok()),
+            round: inputs
+                .value_of("round")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.round),
+            sub_units: inputs.is_present("sub-units"),
+            suffix: inputs.value_of("suffix").map(String::from),
+            suffix_case: inputs
+                .value_of("suffix-case")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.suffix_case),
+            to: inputs
+                .value_of("to")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default.to),
+            to_base_unit: inputs.value_of("to-base-unit").and_then(|s| s.parse().ok()),
+            to_unit: inputs
+                .value_of("to-unit")
+                .and_then(|s| parse_unit_size(s).ok())
+                .unwrap_or(default.to_unit),
+            zero_terminated: inputs.is_present("zero_terminated"),
+        }
+    }
+}
+
+/// Builder for `NumfmtOptions`, checking string settings the same way the command line does.
+#[derive(Debug, Clone, Default)]
+pub struct NumfmtOptionsBuilder {
+    options: NumfmtOptions,
+}
+
+impl NumfmtOptionsBuilder {
+    pub fn accept_grouping(mut self, accept_grouping: bool) -> Self {
+        self.options.accept_grouping = accept_grouping;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.options.debug = debug;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: &str) -> Self {
+        self.options.delimiter = Some(delimiter.to_string());
+        self
+    }
+
+    pub fn extended_prefixes(mut self, extended_prefixes: bool) -> Self {
+        self.options.extended_prefixes = extended_prefixes;
+        self
+    }
+
+    pub fn field(mut self, field: &str) -> Self {
+        self.options.field = field.to_string();
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Self {
+        self.options.format = Some(format.to_string());
+        self
+    }
+
+    pub fn from(mut self, from: Unit) -> Self {
+        self.options.from = from;
+        self
+    }
+
+    pub fn from_unit(mut self, from_unit: u128) -> Self {
+        self.options.from_unit = from_unit;
+        self
+    }
+
+    pub fn grouping(mut self, grouping: bool) -> Self {
+        self.options.grouping = grouping;
+        self
+    }
+
+    pub fn grouping_separator(mut self, grouping_separator: &str) -> Self {
+        self.options.grouping_separator = Some(grouping_separator.to_string());
+        self
+    }
+
+    pub fn header(mut self, header: usize) -> Self {
+        self.options.header = header;
+        self
+    }
+
+    pub fn invalid(mut self, invalid: InvalidMode) -> Self {
+        self.options.invalid = invalid;
+        self
+    }
+
+    pub fn padding(mut self, padding: i64) -> Self {
+        self.options.padding = Some(padding);
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.options.precision = Some(precision);
+        self
+    }
+
+    pub fn round(mut self, round: RoundMethod) -> Self {
+        self.options.round = round;
+        self
+    }
+
+    pub fn sub_units(mut self, sub_units: bool) -> Self {
+        self.options.sub_units = sub_units;
+        self
+    }
+
+    pub fn suffix(mut self, suffix: &str) -> Self {
+        self.options.suffix = Some(suffix.to_string());
+        self
+    }
+
+    pub fn suffix_case(mut self, suffix_case: SuffixCase) -> Self {
+        self.options.suffix_case = suffix_case;
+        self
+    }
+
+    pub fn to(mut self, to: Unit) -> Self {
+        self.options.to = to;
+        self
+    }
+
+    pub fn to_base_unit(mut self, to_base_unit: BaseUnit) -> Self {
+        self.options.to_base_unit = Some(to_base_unit);
+        self
+    }
+
+    pub fn to_unit(mut self, to_unit: u128) -> Self {
+        self.options.to_unit = to_unit;
+        self
+    }
+
+    pub fn zero_terminated(mut self, zero_terminated: bool) -> Self {
+        self.options.zero_terminated = zero_terminated;
+        self
+    }
+
+    pub fn build(self) -> Result<NumfmtOptions, String> {
+        validate_field(self.options.field.clone())?;
+        if let Some(format) = &self.options.format {
+            validate_format(format.clone())?;
+        }
+        if self.options.from_unit == 0 || self.options.to_unit == 0 {
+            return Err(String::from("value should be strickly positive integer."));
+        }
+        Ok(self.options)
+    }
+}
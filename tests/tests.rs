@@ -1,6 +1,5 @@
 use assert_cmd::prelude::*; // Add methods on commands
                             //use predicates::prelude::*; // Used for writing assertions
-use exitcode;
 use std::io::Write;
 use std::process::{Command, Stdio}; // Run programs
 use std::fs;
@@ -45,6 +44,8 @@ fn test_validate_field() {
     assert_eq!(validate_field("6".to_string()), Ok(()));
     assert_ne!(validate_field("a-b".to_string()), Ok(()));
     assert_ne!(validate_field("0-j".to_string()), Ok(()));
+    assert_eq!(validate_field("1,3-5,8-".to_string()), Ok(()));
+    assert_ne!(validate_field("1,,2".to_string()), Ok(()));
 }
 
 #[test]
@@ -59,7 +60,7 @@ fn test_validate_format() {
 
 #[test]
 fn test_validate_unit_from() {
-    let inputs = vec!["auto", "si", "iec", "iec-i"];
+    let inputs = ["auto", "si", "iec", "iec-i"];
     assert!(inputs
         .iter()
         .all(|input| validate_unit_from(input.to_string()) == Ok(())));
@@ -68,7 +69,7 @@ fn test_validate_unit_from() {
 
 #[test]
 fn test_validate_unit_to() {
-    let inputs = vec!["si", "iec", "iec-i"];
+    let inputs = ["si", "iec", "iec-i"];
     assert!(inputs
         .iter()
         .all(|input| validate_unit_to(input.to_string()) == Ok(())));
@@ -77,7 +78,7 @@ fn test_validate_unit_to() {
 
 #[test]
 fn test_validate_invalid() {
-    let inputs = vec!["warn", "abort", "fail", "ignore"];
+    let inputs = ["warn", "abort", "fail", "ignore"];
     assert!(inputs
         .iter()
         .all(|input| validate_invalid(input.to_string()) == Ok(())));
@@ -86,7 +87,7 @@ fn test_validate_invalid() {
 
 #[test]
 fn test_validate_round() {
-    let inputs = vec!["up", "down", "from-zero", "towards-zero", "nearest"];
+    let inputs = ["up", "down", "from-zero", "towards-zero", "nearest"];
     assert!(inputs
         .iter()
         .all(|input| validate_round(input.to_string()) == Ok(())));
@@ -95,16 +96,16 @@ fn test_validate_round() {
 
 #[test]
 fn test_validate_si_suffix() {
-    assert_eq!(validate_si_suffix(&"K".to_string()), true);
-    assert_eq!(validate_si_suffix(&"".to_string()), true);
-    assert_eq!(validate_si_suffix(&"A".to_string()), false);
+    assert!(validate_si_suffix("K"));
+    assert!(validate_si_suffix(""));
+    assert!(!validate_si_suffix("A"));
 }
 
 #[test]
 fn test_validate_ieci_suffix() {
-    assert_eq!(validate_ieci_suffix(&"Ki".to_string()), true);
-    assert_eq!(validate_ieci_suffix(&"".to_string()), true);
-    assert_eq!(validate_ieci_suffix(&"k".to_string()), false);
+    assert!(validate_ieci_suffix("Ki"));
+    assert!(validate_ieci_suffix(""));
+    assert!(!validate_ieci_suffix("k"));
 }
 
 #[test]
@@ -141,6 +142,105 @@ fn test_get_iec_power() {
     assert_eq!((base, power), (2, 60));
 }
 
+#[test]
+fn test_large_powers() {
+    // normalizing large numbers used to overflow u32
+    let mut number = 5e12;
+    let mut base = 10;
+    let mut power = 1;
+    get_si_power(&mut base, &mut power, &mut number, "E");
+    assert_eq!((base, power, number), (10, 30, 5.0));
+
+    let mut number = 3.0 * 2.0_f64.powi(40);
+    let mut base = 2;
+    let mut power = 1;
+    get_iec_power(&mut base, &mut power, &mut number, "Zi");
+    assert_eq!((base, power, number), (2, 110, 3.0));
+
+    let mut number = 1.0;
+    change_system(&2, &10, &80, &mut number);
+    assert_eq!(number, 2.0_f64.powi(80) / 1e24);
+
+    assert_eq!(checked_power(10, 24), Some(10_u128.pow(24)));
+    assert_eq!(checked_power(2, 100), Some(1 << 100));
+    assert_eq!(checked_power(10, 39), None);
+}
+
+#[test]
+fn test_extended_prefixes() {
+    let mut number = 2.0;
+    let mut base = 10;
+    let mut power = 1;
+    get_auto_power(&mut base, &mut power, &mut number, "Q");
+    assert_eq!((base, power), (10, 30));
+    get_auto_power(&mut base, &mut power, &mut number, "Ri");
+    assert_eq!((base, power), (2, 90));
+    assert!(validate_extended_suffix("Qi"));
+    assert!(!validate_si_suffix("R"));
+
+    let mut power = 30;
+    assert_eq!(to_si_power(&10, &mut power), "Q");
+    let mut power = 90;
+    assert_eq!(to_iec_power(true, &2, &mut power), "Ri");
+
+    // R and Q are only known with the opt-in flag
+    let gnu = Formatter::new(&NumfmtOptions::builder().to(Unit::Si).build().unwrap(), ".").unwrap();
+    assert!(gnu.parse_human("1R").is_err());
+    assert_eq!(gnu.format_value(999e24).unwrap(), "999Y");
+    assert!(gnu.format_value(2e27).is_err());
+
+    let options = NumfmtOptions::builder()
+        .to(Unit::Si)
+        .extended_prefixes(true)
+        .build()
+        .unwrap();
+    let extended = Formatter::new(&options, ".").unwrap();
+    assert_eq!(extended.parse_human("1R").unwrap(), 1e27);
+    assert_eq!(extended.format_value(2e27).unwrap(), "2.0R");
+    assert!(extended.format_value(3e33).is_err());
+}
+
+#[test]
+fn test_sub_units() {
+    let mut number = 250.0;
+    let mut base = 2;
+    let mut power = 0;
+    get_auto_power(&mut base, &mut power, &mut number, "m");
+    assert_eq!((base, power), (10, -3));
+    get_si_power(&mut base, &mut power, &mut number, "µ");
+    assert_eq!(power, -6);
+    assert!(validate_sub_unit_suffix("u"));
+    assert!(!validate_sub_unit_suffix("M"));
+
+    let mut power = -9;
+    assert_eq!(to_si_power(&10, &mut power), "n");
+    let mut power = -15;
+    assert_eq!(to_si_power(&10, &mut power), "f");
+
+    let formatter = |options: NumfmtOptions| Formatter::new(&options, ".").unwrap();
+    let si = formatter(NumfmtOptions::builder().to(Unit::Si).build().unwrap());
+    assert_eq!(si.parse_human("250m").unwrap(), 0.25);
+    assert_eq!(si.format_value(0.25).unwrap(), "0");
+    assert_eq!(si.format_value(0.000_123).unwrap(), "0");
+    assert_eq!(si.format_value(9.01).unwrap(), "9");
+    assert_eq!(si.format_value(1.01).unwrap(), "1");
+    assert_eq!(si.format_value(9.96).unwrap(), "10");
+
+    let sub_units = formatter(NumfmtOptions::builder().to(Unit::Si).sub_units(true).build().unwrap());
+    assert_eq!(sub_units.format_value(0.25).unwrap(), "250m");
+    assert_eq!(sub_units.format_value(0.000_001_5).unwrap(), "1.5u");
+    assert_eq!(sub_units.format_value(0.000_999_91).unwrap(), "1.0m");
+    assert_eq!(sub_units.format_value(0.0).unwrap(), "0");
+    assert_eq!(sub_units.format_value(2500.0).unwrap(), "2.5K");
+
+    // sub-units keep the decimals they stand for
+    let none = formatter(NumfmtOptions::default());
+    assert_eq!(none.convert("250m").unwrap(), "0.250");
+    assert_eq!(none.convert("1.5n").unwrap(), "0.0000000015");
+    let iec = formatter(NumfmtOptions::builder().from(Unit::Iec).build().unwrap());
+    assert!(iec.convert("250m").is_err());
+}
+
 #[test]
 fn test_get_auto_power() {
     let mut number = 1.0;
@@ -195,14 +295,14 @@ fn test_get_fields() {
 #[test]
 fn test_padding() {
     assert_eq!(
-        padding(&"64".to_string(), &"Ki".to_string(), &"".to_string(), 8),
+        padding("64", "Ki", "", 8),
         "    64Ki".to_string()
     );
     assert_eq!(
         padding(
-            &"480".to_string(),
-            &"M".to_string(),
-            &" cookies".to_string(),
+            "480",
+            "M",
+            " cookies",
             4
         ),
         "480M cookies".to_string()
@@ -213,28 +313,778 @@ fn test_padding() {
 fn test_formatting() {
     assert_eq!(
         formatting(
-            &"64".to_string(),
-            &"Ki".to_string(),
-            &"".to_string(),
+            "64",
+            "Ki",
+            "",
             "%8f".to_string()
         ),
         "    64Ki".to_string()
     );
     assert_eq!(
         formatting(
-            &"480".to_string(),
-            &"M".to_string(),
-            &" cookies".to_string(),
+            "480",
+            "M",
+            " cookies",
             "%f".to_string()
         ),
         "480M cookies".to_string()
     );
 }
 
+#[test]
+fn test_format_spec() {
+    let spec = "<<%'-10.2f>>".parse::<FormatSpec>().unwrap();
+    assert_eq!(
+        spec,
+        FormatSpec {
+            prefix: "<<".to_string(),
+            suffix: ">>".to_string(),
+            grouping: true,
+            zero_padding: false,
+            left_align: true,
+            width: 10,
+            precision: Some(2),
+        }
+    );
+    let spec = "%%%010f%%".parse::<FormatSpec>().unwrap();
+    assert_eq!((spec.prefix.as_str(), spec.suffix.as_str()), ("%", "%"));
+    assert!(spec.zero_padding);
+    assert_eq!(spec.width, 10);
+    assert_eq!("%.f".parse::<FormatSpec>().unwrap().precision, Some(0));
+    assert_eq!("%f".parse::<FormatSpec>().unwrap(), FormatSpec::default());
+
+    // error messages of GNU numfmt
+    assert_eq!(
+        "".parse::<FormatSpec>(),
+        Err("format '' has no % directive".to_string())
+    );
+    assert_eq!(
+        "hello".parse::<FormatSpec>(),
+        Err("format 'hello' has no % directive".to_string())
+    );
+    assert_eq!(
+        "hello%".parse::<FormatSpec>(),
+        Err("format 'hello%' ends in %".to_string())
+    );
+    assert_eq!(
+        "%d".parse::<FormatSpec>(),
+        Err("invalid format '%d', directive must be %[0]['][-][N][.][N]f".to_string())
+    );
+    assert_eq!(
+        "%f %f".parse::<FormatSpec>(),
+        Err("format '%f %f' has too many % directives".to_string())
+    );
+    assert_eq!(
+        "%123456789012345678901234567890f".parse::<FormatSpec>(),
+        Err("invalid format '%123456789012345678901234567890f' (width overflow)".to_string())
+    );
+    assert!("%018446744073709551615f".parse::<FormatSpec>().is_err());
+    assert!("%9223372036854775808f".parse::<FormatSpec>().is_err());
+    assert!("%2147483648f".parse::<FormatSpec>().is_err());
+    assert_eq!("%-2147483647f".parse::<FormatSpec>().unwrap().padding(), Some(-2147483647));
+    assert!("%%f".parse::<FormatSpec>().is_err());
+}
+
+#[test]
+fn test_format_spec_render() {
+    let render = |format: &str, number: &str, unit: &str| {
+        format.parse::<FormatSpec>().unwrap().render(number, unit, "", 1)
+    };
+    assert_eq!(render("%f", "100", ""), "100");
+    assert_eq!(render("%5f", "100", ""), "  100");
+    assert_eq!(render("%-5f", "100", ""), "100  ");
+    assert_eq!(render("%10f", "8.0", "K"), "      8.0K");
+    assert_eq!(render("%-10f", "8.0", "K"), "8.0K      ");
+    assert_eq!(render("<<%f>>", "42", ""), "<<42>>");
+    assert_eq!(render("%%%f%%", "42", ""), "%42%");
+    assert_eq!(render("%05f", "42", ""), "00042");
+    assert_eq!(render("%05f", "-42", ""), "-0042");
+    assert_eq!(render("%06f", "1.2", "K"), "0001.2K");
+    assert_eq!(render("%-05f", "42", ""), "42   ");
+}
+
+#[test]
+fn test_numfmt_options_builder() {
+    let options = NumfmtOptions::builder()
+        .to(Unit::IecI)
+        .padding(8)
+        .field("2")
+        .build()
+        .unwrap();
+    assert_eq!(options.to, Unit::IecI);
+    assert_eq!(options.padding, Some(8));
+    assert_eq!(options.field, "2".to_string());
+    assert_eq!(options.from, Unit::Auto);
+    assert_eq!(options.invalid, InvalidMode::Abort);
+
+    assert!(NumfmtOptions::builder().field("a-b").build().is_err());
+    assert!(NumfmtOptions::builder().format("%af").build().is_err());
+    assert!(NumfmtOptions::builder().to(Unit::Auto).build().is_ok());
+}
+
+#[test]
+fn test_numfmt_options_from_arg_matches() {
+    let inputs = clap::App::new("numfmt")
+        .arg(clap::Arg::with_name("to").long("to").takes_value(true))
+        .arg(clap::Arg::with_name("round").long("round").takes_value(true))
+        .arg(clap::Arg::with_name("grouping").long("grouping"))
+        .get_matches_from(vec!["numfmt", "--to=iec", "--round=down", "--grouping"]);
+    let options = NumfmtOptions::from(&inputs);
+    assert_eq!(options.to, Unit::Iec);
+    assert_eq!(options.round, RoundMethod::Down);
+    assert!(options.grouping);
+    assert_eq!(options.padding, None);
+}
+
+#[test]
+fn test_numfmt_with_options() {
+    let options = NumfmtOptions::builder()
+        .to(Unit::Si)
+        .field("1")
+        .build()
+        .unwrap();
+    let mut output = Vec::new();
+    numfmt("2000 x".to_string(), &options, ".", &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "2.0K x\n");
+}
+
+#[test]
+fn test_numfmt_error() {
+    let options = NumfmtOptions::default();
+    match numfmt_core("12a".to_string(), &options, ".") {
+        Err(NumfmtError::UnknownSuffix { field, .. }) => assert_eq!(field, "12a"),
+        other => panic!("unexpected result {:?}", other),
+    }
+    match numfmt_core("abc".to_string(), &options, ".") {
+        Err(NumfmtError::InvalidNumber { field, .. }) => assert_eq!(field, "abc"),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut output = Vec::new();
+    match numfmt("1 abc".to_string(), &options, ".", &mut output) {
+        Err(err) => {
+            assert_eq!(err.exit_code(), EXIT_CONVERSION_FAILURE);
+            assert_eq!(
+                err.with_line(3).to_string(),
+                "invalid number: 'abc' (line 3, column 3)"
+            );
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let options = NumfmtOptions::builder()
+        .invalid(InvalidMode::Warn)
+        .build()
+        .unwrap();
+    let mut output = Vec::new();
+    let warnings = numfmt("1 abc 2".to_string(), &options, ".", &mut output).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(String::from_utf8(output).unwrap(), "1 abc 2\n");
+
+    // short lines are written unchanged, and only reported with --debug
+    let options = NumfmtOptions::builder().field("3").build().unwrap();
+    let mut output = Vec::new();
+    let warnings = numfmt("1 2".to_string(), &options, ".", &mut output).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(String::from_utf8(output).unwrap(), "1 2\n");
+    let options = NumfmtOptions::builder()
+        .field("3")
+        .debug(true)
+        .invalid(InvalidMode::Fail)
+        .build()
+        .unwrap();
+    let warnings = numfmt("1 2".to_string(), &options, ".", Vec::new()).unwrap();
+    assert!(matches!(
+        warnings[..],
+        [NumfmtError::FieldOutOfRange { field: 3, .. }]
+    ));
+}
+
+#[test]
+fn test_formatter() {
+    let options = NumfmtOptions::builder()
+        .from(Unit::Iec)
+        .to(Unit::IecI)
+        .field("2")
+        .build()
+        .unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.parse_human("2K").unwrap(), 2048.0);
+    assert_eq!(formatter.format_value(4096.0).unwrap(), "4.0Ki".to_string());
+    // values no suffix can hold are not printed
+    for value in [f64::INFINITY, f64::NAN, 1e300] {
+        assert!(matches!(
+            formatter.format_value(value),
+            Err(NumfmtError::OutputOverflow { .. })
+        ));
+    }
+    match formatter.convert("1e300") {
+        Err(NumfmtError::OutputOverflow { field, .. }) => assert_eq!(field, "1e300"),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut output = Vec::new();
+    for line in ["a 2048 b", "c 3Ki d"].iter() {
+        formatter.process_line(line, &mut output).unwrap();
+    }
+    assert_eq!(String::from_utf8(output).unwrap(), "a 2.0Ki b\nc 3.0Ki d\n");
+
+    let options = NumfmtOptions {
+        format: Some("%".to_string()),
+        ..NumfmtOptions::default()
+    };
+    assert!(matches!(
+        Formatter::new(&options, "."),
+        Err(NumfmtError::InvalidFormatSpec(_))
+    ));
+}
+
+#[test]
+fn test_unit_none() {
+    let options = NumfmtOptions::builder()
+        .from(Unit::None)
+        .suffix("B")
+        .build()
+        .unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("1500").unwrap(), "1500B");
+    assert_eq!(formatter.convert("1500B").unwrap(), "1500B");
+    match formatter.convert("1K") {
+        Err(NumfmtError::RejectedSuffix { field, .. }) => assert_eq!(field, "1K"),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(formatter.convert("1KB").is_err());
+
+    // no scaling on output, but the unit size, grouping and padding still apply
+    let options = NumfmtOptions::builder()
+        .to(Unit::None)
+        .to_unit(10)
+        .grouping(true)
+        .padding(8)
+        .build()
+        .unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.format_value(12_345_670.0).unwrap(), "1,234,567");
+    assert_eq!(formatter.format_value(1230.0).unwrap(), "     123");
+}
+
+#[test]
+fn test_unit_size() {
+    assert_eq!(parse_unit_size("512"), Ok(512));
+    assert_eq!(parse_unit_size("1K"), Ok(1000));
+    assert_eq!(parse_unit_size("4Ki"), Ok(4096));
+    assert!(parse_unit_size("0").is_err());
+    assert!(parse_unit_size("1.5K").is_err());
+    assert!(parse_unit_size("-1").is_err());
+    assert!(parse_unit_size("1X").is_err());
+    assert_eq!(parse_unit_size("1Y"), Ok(10_u128.pow(24)));
+    assert!(parse_unit_size("1000000000000000Y").is_err());
+    assert!(validate_unit_size("2Mi".to_string()).is_ok());
+
+    let options = NumfmtOptions::builder()
+        .from_unit(1024)
+        .to_unit(512)
+        .build()
+        .unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.parse_human("2").unwrap(), 2048.0);
+    assert_eq!(formatter.convert("2").unwrap(), "4");
+    assert!(NumfmtOptions::builder().from_unit(0).build().is_err());
+    assert!(NumfmtOptions::builder().to_unit(0).build().is_err());
+
+    // options written as a struct literal scale the same way
+    let options = NumfmtOptions { from_unit: 512, ..NumfmtOptions::default() };
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.parse_human("2").unwrap(), 1024.0);
+    assert_eq!(formatter.convert("2").unwrap(), "1024");
+    let options = NumfmtOptions { to_unit: 1000, ..NumfmtOptions::default() };
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("5000").unwrap(), "5");
+}
+
+#[test]
+fn test_field_selector() {
+    let fields = "1,3-5,8-".parse::<FieldSelector>().unwrap();
+    for (index, selected) in [(1, true), (2, false), (3, true), (5, true), (6, false), (9, true)].iter() {
+        assert_eq!(fields.contains(*index), *selected, "field {}", index);
+    }
+    assert_eq!(fields.first(), 1);
+
+    let fields = "-3".parse::<FieldSelector>().unwrap();
+    assert!(fields.contains(1) && fields.contains(3) && !fields.contains(4));
+    assert_eq!("-".parse::<FieldSelector>(), Ok(FieldSelector::all()));
+    assert_eq!("4-6,2".parse::<FieldSelector>().unwrap().first(), 2);
+
+    assert_eq!("0".parse::<FieldSelector>(), Err("fields are numbered from 1".to_string()));
+    assert_eq!(
+        "5-2".parse::<FieldSelector>(),
+        Err("invalid decreasing range '5-2'".to_string())
+    );
+    assert!("a".parse::<FieldSelector>().is_err());
+    assert!("1-2-3".parse::<FieldSelector>().is_err());
+
+    let options = NumfmtOptions::builder()
+        .to(Unit::Si)
+        .field("1,3")
+        .build()
+        .unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    let mut output = Vec::new();
+    formatter.process_line("1000 2000 3000", &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "1.0K 2000 3.0K\n");
+}
+
+#[test]
+fn test_exact_value() {
+    assert_eq!(get_suffix_power(Unit::Si, "K"), (10, 3));
+    assert_eq!(get_suffix_power(Unit::Iec, "K"), (2, 10));
+    assert_eq!(get_suffix_power(Unit::Auto, "Mi"), (2, 20));
+    assert_eq!(get_suffix_power(Unit::None, ""), (10, 0));
+
+    assert_eq!(get_exact_value("16", 2, 60, 1), Some(1 << 64));
+    assert_eq!(get_exact_value("1.5", 2, 10, 1), Some(1536));
+    assert_eq!(get_exact_value("9007199254740993", 10, 0, 1), Some(9_007_199_254_740_993));
+    assert_eq!(get_exact_value("2", 10, 3, 512), Some(1_024_000));
+    assert_eq!(get_exact_value("1.25", 10, 0, 1), None);
+    assert_eq!(get_exact_value("1", 10, 40, 1), None);
+
+    let options = NumfmtOptions::builder().from(Unit::Iec).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("16E").unwrap(), "18446744073709551616");
+    assert_eq!(formatter.convert("9007199254740993").unwrap(), "9007199254740993");
+}
+
+#[test]
+fn test_k8s_quantity() {
+    let quantity = |s: &str| s.parse::<Quantity>().unwrap();
+    assert_eq!(
+        quantity("512Mi"),
+        Quantity { nanos: 512 * 1024 * 1024 * 1_000_000_000, format: QuantityFormat::BinarySI }
+    );
+    assert_eq!(quantity("250m").nanos, 250_000_000);
+    assert_eq!(quantity("+.5").nanos, 500_000_000);
+    assert_eq!(quantity("1.").nanos, 1_000_000_000);
+    assert_eq!(quantity("-1.5k").nanos, -1_500_000_000_000);
+    assert_eq!(quantity("1e3").format, QuantityFormat::DecimalExponent);
+    assert_eq!(quantity("1E").format, QuantityFormat::DecimalSI);
+    // amounts below a nano are rounded up
+    assert_eq!(quantity("1e-12").nanos, 1);
+    assert_eq!(quantity("1.5Gi").precision(), 0);
+    assert_eq!(quantity("0.125").precision(), 3);
+
+    for invalid in [".", "Mi", "1K", "1mi", "1e", "1.2.3", "1 k"].iter() {
+        assert!(invalid.parse::<Quantity>().is_err(), "{}", invalid);
+    }
+    assert!(matches!("1e99".parse::<Quantity>(), Err(NumfmtError::Overflow { .. })));
+
+    let canonical = |s: &str| {
+        let (number, suffix) = quantity(s).canonical();
+        number + &suffix
+    };
+    assert_eq!(canonical("1.5Gi"), "1536Mi");
+    assert_eq!(canonical("1024Ki"), "1Mi");
+    assert_eq!(canonical("1000Ki"), "1000Ki");
+    assert_eq!(canonical("0.5Ki"), "512");
+    assert_eq!(canonical("1.5"), "1500m");
+    assert_eq!(canonical("2000"), "2k");
+    assert_eq!(canonical("1500"), "1500");
+    assert_eq!(canonical("0.1u"), "100n");
+    assert_eq!(canonical("1000E"), "1000E");
+    assert_eq!(canonical("1000e3"), "1e6");
+    assert_eq!(canonical("0"), "0");
+}
+
+#[test]
+fn test_suffix_case() {
+    assert_eq!(normalize_suffix_case(Unit::Auto, "k").as_deref(), Some("K"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "ki").as_deref(), Some("Ki"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "KI").as_deref(), Some("Ki"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "M").as_deref(), Some("M"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "U").as_deref(), Some("u"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "x").as_deref(), Some("x"));
+    assert_eq!(normalize_suffix_case(Unit::Auto, "m"), None);
+    assert_eq!(normalize_suffix_case(Unit::Auto, "p"), None);
+    // without sub-units, m and p can only be mega and peta
+    assert_eq!(normalize_suffix_case(Unit::Iec, "m").as_deref(), Some("M"));
+    assert_eq!(normalize_suffix_case(Unit::IecI, "p").as_deref(), Some("P"));
+    assert!(validate_suffix_case(String::from("insensitive")).is_ok());
+    assert!(validate_suffix_case(String::from("lower")).is_err());
+
+    let options = NumfmtOptions::builder()
+        .suffix_case(SuffixCase::Insensitive)
+        .build()
+        .unwrap();
+    let insensitive = Formatter::new(&options, ".").unwrap();
+    assert_eq!(insensitive.parse_human("1k").unwrap(), 1000.0);
+    assert_eq!(insensitive.parse_human("2gi").unwrap(), 2147483648.0);
+    assert!(matches!(
+        insensitive.parse_human("1m"),
+        Err(NumfmtError::AmbiguousSuffix { .. })
+    ));
+
+    let strict = Formatter::new(&NumfmtOptions::default(), ".").unwrap();
+    assert!(strict.parse_human("1k").is_err());
+}
+
+#[test]
+fn test_base_units() {
+    assert_eq!(split_base_unit("GB"), ("G", "B"));
+    assert_eq!(split_base_unit("Gbps"), ("G", "bps"));
+    assert_eq!(split_base_unit("Mbit/s"), ("M", "bit/s"));
+    assert_eq!(split_base_unit(" MiB"), ("Mi", "B"));
+    assert_eq!(split_base_unit("K/s"), ("K", "/s"));
+    assert_eq!(split_base_unit("bytes"), ("", "bytes"));
+    assert_eq!(split_base_unit("Ki"), ("Ki", ""));
+    assert_eq!(split_base_unit(" K"), (" K", ""));
+
+    let options = NumfmtOptions::builder().to(Unit::IecI).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.parse_human("1.5GB").unwrap(), 1.5e9);
+    assert_eq!(formatter.convert("1.5GB").unwrap(), "1.4GiB");
+    assert_eq!(formatter.convert("2048Hz").unwrap(), "2.0KiHz");
+    assert!(formatter.convert("1XB").is_err());
+
+    let none = Formatter::new(&NumfmtOptions::default(), ".").unwrap();
+    assert_eq!(none.convert("2KiB").unwrap(), "2048B");
+
+    // without a prefix to read, base units are suffixes like any other
+    let options = NumfmtOptions::builder().from(Unit::None).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    for field in ["12B", "10Hz", "5bytes"] {
+        assert!(matches!(
+            formatter.convert(field),
+            Err(NumfmtError::RejectedSuffix { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_to_base_unit() {
+    assert!(validate_base_unit(String::from("bits")).is_ok());
+    assert!(validate_base_unit(String::from("nibbles")).is_err());
+
+    let formatter = |target: BaseUnit| {
+        let options = NumfmtOptions::builder().to_base_unit(target).build().unwrap();
+        Formatter::new(&options, ".").unwrap()
+    };
+    let bytes = formatter(BaseUnit::Bytes);
+    assert_eq!(bytes.convert("1Kb").unwrap(), "125B");
+    assert_eq!(bytes.convert("100bit").unwrap(), "12.5B");
+    assert_eq!(bytes.convert("1b").unwrap(), "0.125B");
+    assert_eq!(bytes.convert("8Kbps").unwrap(), "1000B/s");
+    assert_eq!(bytes.convert("3KB").unwrap(), "3000B");
+    assert_eq!(bytes.convert("5Hz").unwrap(), "5Hz");
+
+    let bits = formatter(BaseUnit::Bits);
+    assert_eq!(bits.convert("1KiB").unwrap(), "8192bit");
+    assert_eq!(bits.convert("2B/s").unwrap(), "16bit/s");
+    assert_eq!(bits.convert("7").unwrap(), "7");
+}
+
+#[test]
+fn test_exponent_notation() {
+    let strip = |input: &str| {
+        let mut number = input.to_string();
+        let mut suffix = String::new();
+        let value = strip_number(&mut number, &mut suffix).ok();
+        (value, number, suffix)
+    };
+    assert_eq!(strip("1e6"), (Some(1e6), "1e6".to_string(), String::new()));
+    assert_eq!(strip("+3e+2K"), (Some(300.0), "+3e+2".to_string(), "K".to_string()));
+    assert_eq!(strip("-2.5E-3"), (Some(-0.0025), "-2.5E-3".to_string(), String::new()));
+    // without digits, E is the exa suffix
+    assert_eq!(strip("1E"), (Some(1.0), "1".to_string(), "E".to_string()));
+    assert_eq!(strip("2Ei"), (Some(2.0), "2".to_string(), "Ei".to_string()));
+
+    assert_eq!(split_exponent("2.5E-3"), ("2.5", -3));
+    assert_eq!(split_exponent("12"), ("12", 0));
+    assert_eq!(split_exponent("1e99999999999"), ("1", i32::MAX));
+    assert_eq!(get_exact_value("1.5e3", 10, 0, 1), Some(1500));
+    assert_eq!(get_exact_value("1e30", 10, 0, 1), Some(10_u128.pow(30)));
+    assert_eq!(get_exact_value("15e-1", 10, 0, 1), None);
+
+    let formatter = Formatter::new(&NumfmtOptions::default(), ".").unwrap();
+    assert_eq!(formatter.convert("2.5E-3").unwrap(), "0.0025");
+    assert_eq!(formatter.convert("1.5e1").unwrap(), "15");
+    assert_eq!(formatter.convert("+12").unwrap(), "12");
+    assert_eq!(formatter.convert("2e3K").unwrap(), "2000000");
+    assert!(matches!(formatter.convert("1e400"), Err(NumfmtError::Overflow { .. })));
+    assert!(matches!(formatter.convert("1e-400"), Err(NumfmtError::Underflow { .. })));
+    assert!(matches!(formatter.convert("1e-310f"), Err(NumfmtError::Underflow { .. })));
+}
+
+#[test]
+fn test_negative_numbers() {
+    let mut number = -2500.0;
+    let (mut base, mut power) = (0, 0);
+    get_si_power(&mut base, &mut power, &mut number, "K");
+    assert_eq!((power, number), (6, -2.5));
+    let mut number = -2048.0;
+    get_iec_power(&mut base, &mut power, &mut number, "");
+    assert_eq!((power, number), (10, -2.0));
+    assert_eq!(get_exact_value("-1.5", 2, 10, 1), Some(1536));
+
+    let inputs = ["-2000", "-1500", "-1.25", "-0.5", "-1K"];
+    let cases = [
+        (Unit::None, RoundMethod::Up, ["-2000", "-1500", "-1.25", "-0.5", "-1000"]),
+        (Unit::Si, RoundMethod::Up, ["-2.0K", "-1.5K", "-1", "0", "-1.0K"]),
+        (Unit::Si, RoundMethod::Down, ["-2.0K", "-1.5K", "-1", "0", "-1.0K"]),
+        (Unit::Si, RoundMethod::FromZero, ["-2.0K", "-1.5K", "-1", "0", "-1.0K"]),
+        (Unit::Si, RoundMethod::TowardsZero, ["-2.0K", "-1.5K", "-1", "0", "-1.0K"]),
+        (Unit::Si, RoundMethod::Nearest, ["-2.0K", "-1.5K", "-1", "0", "-1.0K"]),
+        (Unit::Iec, RoundMethod::Up, ["-1.9K", "-1.4K", "-1", "0", "-1000"]),
+        (Unit::Iec, RoundMethod::Down, ["-2.0K", "-1.5K", "-1", "0", "-1000"]),
+        (Unit::Iec, RoundMethod::FromZero, ["-2.0K", "-1.5K", "-1", "0", "-1000"]),
+        (Unit::Iec, RoundMethod::TowardsZero, ["-1.9K", "-1.4K", "-1", "0", "-1000"]),
+        (Unit::Iec, RoundMethod::Nearest, ["-2.0K", "-1.5K", "-1", "0", "-1000"]),
+        (Unit::IecI, RoundMethod::Up, ["-1.9Ki", "-1.4Ki", "-1", "0", "-1000"]),
+        (Unit::IecI, RoundMethod::Down, ["-2.0Ki", "-1.5Ki", "-1", "0", "-1000"]),
+        (Unit::IecI, RoundMethod::FromZero, ["-2.0Ki", "-1.5Ki", "-1", "0", "-1000"]),
+        (Unit::IecI, RoundMethod::TowardsZero, ["-1.9Ki", "-1.4Ki", "-1", "0", "-1000"]),
+        (Unit::IecI, RoundMethod::Nearest, ["-2.0Ki", "-1.5Ki", "-1", "0", "-1000"]),
+        (Unit::K8s, RoundMethod::Up, ["-2k", "-1500", "-1250m", "-500m", "-1k"]),
+    ];
+    for (to, round, expected) in cases.iter() {
+        let options = NumfmtOptions::builder().to(*to).round(*round).build().unwrap();
+        let formatter = Formatter::new(&options, ".").unwrap();
+        for (input, output) in inputs.iter().zip(expected.iter()) {
+            assert_eq!(formatter.convert(input).unwrap(), *output, "{:?} {:?} {}", to, round, input);
+        }
+    }
+    // numbers written without scaling are not rounded
+    let rounds = [
+        RoundMethod::Down,
+        RoundMethod::FromZero,
+        RoundMethod::TowardsZero,
+        RoundMethod::Nearest,
+    ];
+    for round in rounds.iter() {
+        for (to, expected) in [(Unit::None, "-1.25"), (Unit::K8s, "-1250m")].iter() {
+            let options = NumfmtOptions::builder().to(*to).round(*round).build().unwrap();
+            let formatter = Formatter::new(&options, ".").unwrap();
+            assert_eq!(formatter.convert("-1.25").unwrap(), *expected);
+        }
+    }
+
+    let formatter = Formatter::new(&NumfmtOptions::default(), ".").unwrap();
+    assert_eq!(formatter.convert("-123456789012345678901234").unwrap(), "-123456789012345678901234");
+    assert_eq!(formatter.convert("-0").unwrap(), "0");
+    let options = NumfmtOptions::builder().grouping(true).padding(10).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("-123456").unwrap(), "  -123,456");
+}
+
+#[test]
+fn test_locale() {
+    assert_eq!(Locale::default(), Locale::c());
+    assert_eq!(Locale::from_name("C.UTF-8"), Some(Locale::c()));
+    let german = Locale::from_name("de_DE.UTF-8@euro").unwrap();
+    assert_eq!((german.decimal_point.as_str(), german.thousands_sep.as_str()), (",", "."));
+    assert_eq!(Locale::from_name("hi_IN").unwrap().grouping, vec![3, 2]);
+    // unknown countries fall back on their language
+    assert_eq!(Locale::from_name("fr_CA.UTF-8"), Locale::from_name("fr_FR"));
+    assert_eq!(Locale::from_name("xx_XX"), None);
+
+    let options = NumfmtOptions::builder().grouping(true).build().unwrap();
+    let formatter = Formatter::with_locale(&options, &german).unwrap();
+    assert_eq!(formatter.parse_human("1,5K").unwrap(), 1500.0);
+    assert_eq!(formatter.convert("1234567").unwrap(), "1.234.567");
+    let formatter = Formatter::with_locale(&NumfmtOptions::default(), &german).unwrap();
+    assert_eq!(formatter.convert("0,25").unwrap(), "0,25");
+    // a literal "." is the thousands separator, not a decimal point
+    assert!(formatter.convert("1.234").is_err());
+    assert!(formatter.convert("1234567.5").is_err());
+    let formatter = Formatter::with_locale(&options, &Locale::c()).unwrap();
+    assert_eq!(formatter.convert("1234567").unwrap(), "1234567");
+    assert!(matches!(
+        formatter.convert("1,234"),
+        Err(NumfmtError::UnknownSuffix { .. })
+    ));
+}
+
+#[test]
+fn test_grouping() {
+    let locale = |name: &str| Locale::from_name(name).unwrap();
+    assert_eq!(locale("en_US").group_digits("1234567"), "1,234,567");
+    assert_eq!(locale("en_US").group_digits("123"), "123");
+    assert_eq!(locale("hi_IN").group_digits("123456789"), "12,34,56,789");
+    assert_eq!(locale("de_DE").group_digits("1000"), "1.000");
+    assert_eq!(locale("C").group_digits("1234567"), "1234567");
+
+    let options = NumfmtOptions::builder().grouping(true).build().unwrap();
+    let formatter = Formatter::with_locale(&options, &locale("en_US")).unwrap();
+    assert_eq!(formatter.convert("1234.5").unwrap(), "1,234.5");
+    assert_eq!(formatter.convert("-123456.125").unwrap(), "-123,456.125");
+    let formatter = Formatter::with_locale(&options, &locale("hi_IN")).unwrap();
+    assert_eq!(formatter.convert("10000000").unwrap(), "1,00,00,000");
+    let formatter = Formatter::with_locale(&options, &locale("fr_FR")).unwrap();
+    assert_eq!(formatter.convert("1234,5").unwrap(), "1\u{202f}234,5");
+
+    let options = NumfmtOptions::builder().grouping(true).grouping_separator("_").build().unwrap();
+    let formatter = Formatter::with_locale(&options, &Locale::c()).unwrap();
+    assert_eq!(formatter.convert("1234567").unwrap(), "1_234_567");
+    let formatter = Formatter::with_locale(&options, &locale("hi_IN")).unwrap();
+    assert_eq!(formatter.convert("1234567").unwrap(), "12_34_567");
+
+    // a comma decimal point groups digits with dots
+    let options = NumfmtOptions::builder().grouping(true).build().unwrap();
+    let formatter = Formatter::new(&options, ",").unwrap();
+    assert_eq!(formatter.convert("1234567,5").unwrap(), "1.234.567,5");
+}
+
+#[test]
+fn test_accept_grouping() {
+    let locale = |name: &str| Locale::from_name(name).unwrap();
+    let english = locale("en_US");
+    assert_eq!(english.ungroup("1,234,567").as_deref(), Some("1234567"));
+    assert_eq!(english.ungroup("-12,345.5K").as_deref(), Some("-12345.5K"));
+    assert_eq!(english.ungroup("1234").as_deref(), Some("1234"));
+    assert_eq!(english.ungroup("1,234,").as_deref(), Some("1234,"));
+    assert_eq!(english.ungroup("12,34,5"), None);
+    assert_eq!(english.ungroup("1,2345"), None);
+    assert_eq!(english.ungroup("1234,567"), None);
+    assert_eq!(locale("hi_IN").ungroup("12,34,567").as_deref(), Some("1234567"));
+    assert_eq!(locale("hi_IN").ungroup("1,234,567"), None);
+    assert_eq!(locale("fr_FR").ungroup("1 234\u{202f}567,5").as_deref(), Some("1234567,5"));
+    assert_eq!(Locale::c().ungroup("1,234"), Some("1,234".to_string()));
+
+    let options = NumfmtOptions::builder().accept_grouping(true).build().unwrap();
+    let formatter = Formatter::with_locale(&options, &english).unwrap();
+    assert_eq!(formatter.convert("1,234,567").unwrap(), "1234567");
+    assert!(matches!(
+        formatter.convert("12,34,5"),
+        Err(NumfmtError::InvalidNumber { .. })
+    ));
+    let formatter = Formatter::with_locale(&options, &locale("de_DE")).unwrap();
+    assert_eq!(formatter.convert("1.234,5").unwrap(), "1234,5");
+    assert!(formatter.convert("1.5").is_err());
+    let formatter = Formatter::new(&options, ",").unwrap();
+    assert_eq!(formatter.convert("1,5").unwrap(), "1,5");
+    assert_eq!(formatter.convert("1.234,5").unwrap(), "1234,5");
+}
+
+#[test]
+fn test_to_auto() {
+    assert!(validate_unit_to(String::from("auto")).is_ok());
+
+    let options = NumfmtOptions::builder().to(Unit::Auto).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("2048Ki").unwrap(), "2.0Mi");
+    assert_eq!(formatter.convert("3000K").unwrap(), "3.0M");
+    assert_eq!(formatter.convert("1536MiB").unwrap(), "1.5GiB");
+    assert_eq!(formatter.convert("1500").unwrap(), "1500");
+    assert_eq!(formatter.format_value(1500.0).unwrap(), "1500");
+
+    let options = NumfmtOptions::builder().from(Unit::Iec).to(Unit::Auto).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("2048K").unwrap(), "2.0M");
+
+    let options = NumfmtOptions::builder().from(Unit::K8s).to(Unit::Auto).build().unwrap();
+    let formatter = Formatter::new(&options, ".").unwrap();
+    assert_eq!(formatter.convert("2048Ki").unwrap(), "2.0Mi");
+    assert_eq!(formatter.convert("1e3").unwrap(), "1000");
+}
+
+#[test]
+fn test_round_method() {
+    let methods = [
+        RoundMethod::Up,
+        RoundMethod::Down,
+        RoundMethod::FromZero,
+        RoundMethod::TowardsZero,
+        RoundMethod::Nearest,
+    ];
+    let cases = [
+        (1.21, [1.3, 1.2, 1.3, 1.2, 1.2]),
+        (1.25, [1.3, 1.2, 1.3, 1.2, 1.3]),
+        (-1.21, [-1.2, -1.3, -1.3, -1.2, -1.2]),
+        (-1.25, [-1.2, -1.3, -1.3, -1.2, -1.3]),
+        // exact values are left untouched
+        (0.7, [0.7, 0.7, 0.7, 0.7, 0.7]),
+        (-4.0, [-4.0, -4.0, -4.0, -4.0, -4.0]),
+    ];
+    for (value, expected) in cases.iter() {
+        for (method, result) in methods.iter().zip(expected.iter()) {
+            assert_eq!(method.round(*value, 1), *result, "{:?} of {}", method, value);
+        }
+    }
+    assert_eq!(RoundMethod::FromZero.round(42.0, 0), 42.0);
+    assert_eq!(RoundMethod::FromZero.round(42.1, 0), 43.0);
+    assert_eq!(RoundMethod::Nearest.round(2.5, 0), 3.0);
+    assert_eq!(RoundMethod::Down.round(-2.5, 0), -3.0);
+}
+
+#[test]
+fn test_round_after_scaling() {
+    let formatter = |round: RoundMethod| {
+        let options = NumfmtOptions::builder()
+            .to(Unit::Si)
+            .round(round)
+            .build()
+            .unwrap();
+        Formatter::new(&options, ".").unwrap()
+    };
+    assert_eq!(formatter(RoundMethod::Up).format_value(1210.0).unwrap(), "1.3K");
+    assert_eq!(formatter(RoundMethod::Down).format_value(1290.0).unwrap(), "1.2K");
+    assert_eq!(formatter(RoundMethod::FromZero).format_value(1000.0).unwrap(), "1.0K");
+    assert_eq!(formatter(RoundMethod::Nearest).format_value(1250.0).unwrap(), "1.3K");
+    assert_eq!(formatter(RoundMethod::TowardsZero).format_value(1290.0).unwrap(), "1.2K");
+    // rounding can reach the next suffix or drop the decimal
+    assert_eq!(formatter(RoundMethod::FromZero).format_value(999.1).unwrap(), "1.0K");
+    assert_eq!(formatter(RoundMethod::FromZero).format_value(9960.0).unwrap(), "10K");
+    assert_eq!(formatter(RoundMethod::Down).format_value(9960.0).unwrap(), "9.9K");
+}
+
+#[test]
+fn test_precision() {
+    let formatter = |options: NumfmtOptions| Formatter::new(&options, ".").unwrap();
+
+    let si = formatter(NumfmtOptions::builder().to(Unit::Si).build().unwrap());
+    assert_eq!(si.format_value(1500.0).unwrap(), "1.5K");
+    assert_eq!(si.format_value(15000.0).unwrap(), "15K");
+    assert_eq!(si.format_value(999.0).unwrap(), "999");
+    assert_eq!(si.format_value(1.0).unwrap(), "1");
+
+    let precise = formatter(
+        NumfmtOptions::builder()
+            .to(Unit::Si)
+            .precision(3)
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(precise.format_value(15000.0).unwrap(), "15.000K");
+
+    // the precision of --format overrides --precision
+    let formatted = formatter(
+        NumfmtOptions::builder()
+            .to(Unit::Si)
+            .precision(3)
+            .format("%.2f")
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(formatted.format_value(1230.0).unwrap(), "1.23K");
+
+    // without --to, the input precision is kept unless a suffix scaled the number
+    let kept = formatter(NumfmtOptions::default());
+    assert_eq!(kept.convert("1.50").unwrap(), "1.50");
+    assert_eq!(kept.convert("7.4K").unwrap(), "7400");
+}
+
 /* =====================
 *  | Integration tests |
 *  =====================
 */
+fn numfmt_command() -> Result<Command, Box<dyn std::error::Error>> {
+    /*
+     * The numfmt binary in the C locale, so that tests do not depend on the
+     * locale they run in. Tests of other locales set LC_ALL again.
+     */
+    let mut cmd = Command::cargo_bin(NUMFMT)?;
+    cmd.env("LC_ALL", "C");
+    Ok(cmd)
+}
+
 fn pipe_command(
     command: &str,
     cmd_args: Vec<String>,
@@ -258,6 +1108,7 @@ fn pipe_command(
     println!("First program output: {:?}", pipe);
     let mut child = Command::new(prog)
         .args(prog_args)
+        .env("LC_ALL", "C")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -269,22 +1120,22 @@ fn pipe_command(
     });
 
     let output = child.wait_with_output();
-    return match output {
+    match output {
         Ok(res) => Ok(String::from_utf8_lossy(&res.stdout).to_string()),
         Err(e) => {eprintln!("{}", e); Err(Box::new(e))},
-    };
+    }
 }
 
 #[test]
 fn missing_argument() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
+    let mut cmd = numfmt_command()?;
     cmd.assert().failure().code(exitcode::NOINPUT);
     Ok(())
 }
 
 #[test]
 fn basic() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
+    let mut cmd = numfmt_command()?;
     let prog = cmd.arg("42").assert();
     prog.success().stdout("42\n");
     Ok(())
@@ -292,7 +1143,7 @@ fn basic() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn help() -> Result<(), Box<dyn std::error::Error>> {
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
+    let mut cmd = numfmt_command()?;
     let prog = cmd.arg("--help").assert();
     prog.success();
     Ok(())
@@ -313,27 +1164,27 @@ fn basic_pipe() -> Result<(), Box<dyn std::error::Error>> {
 #[test]
 fn test_example0() -> Result<(), Box<dyn std::error::Error>> {
     //numfmt --to=si 1000 -> "1.0K"
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
-    let prog = cmd.args(&["--to=si", "1000"]).assert();
-    prog.success().stdout("1K\n");
+    let mut cmd = numfmt_command()?;
+    let prog = cmd.args(["--to=si", "1000"]).assert();
+    prog.success().stdout("1.0K\n");
     Ok(())
 }
 
 #[test]
 fn test_example1() -> Result<(), Box<dyn std::error::Error>> {
     //numfmt --to=iec 2048 -> "2.0K"
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
-    let prog = cmd.args(&["--to=iec", "2048"]).assert();
-    prog.success().stdout("2K\n");
+    let mut cmd = numfmt_command()?;
+    let prog = cmd.args(["--to=iec", "2048"]).assert();
+    prog.success().stdout("2.0K\n");
     Ok(())
 }
 
 #[test]
 fn test_example2() -> Result<(), Box<dyn std::error::Error>> {
     //numfmt --to=iec-i 4096 -> "4.0Ki"
-    let mut cmd = Command::cargo_bin(NUMFMT)?;
-    let prog = cmd.args(&["--to=iec-i", "4096"]).assert();
-    prog.success().stdout("4Ki\n");
+    let mut cmd = numfmt_command()?;
+    let prog = cmd.args(["--to=iec-i", "4096"]).assert();
+    prog.success().stdout("4.0Ki\n");
     Ok(())
 }
 
@@ -341,7 +1192,7 @@ fn test_example2() -> Result<(), Box<dyn std::error::Error>> {
 fn test_example3() -> Result<(), Box<dyn std::error::Error>> {
     //echo 1K | numfmt --from=si -> "1000"
     let stdout = pipe_command("echo", vec!["1K".to_string()], BIN_NUMFMT, vec![])?;
-    assert_eq!(stdout, "1K\n");
+    assert_eq!(stdout, "1000\n");
     Ok(())
 }
 
@@ -354,7 +1205,7 @@ fn test_example4() -> Result<(), Box<dyn std::error::Error>> {
         BIN_NUMFMT,
         vec!["--from=iec".to_string()],
     )?;
-    assert_eq!(stdout, "1.024K\n");
+    assert_eq!(stdout, "1024\n");
     Ok(())
 }
 
@@ -435,3 +1286,456 @@ fn test_example8() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(stdout, expected);
     Ok(())
 }
+
+#[test]
+fn invalid_modes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.arg("abc")
+        .assert()
+        .failure()
+        .code(EXIT_CONVERSION_FAILURE)
+        .stderr("numfmt: invalid number: 'abc' (line 1, column 1)\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--invalid=fail", "abc"])
+        .assert()
+        .failure()
+        .code(EXIT_CONVERSION_FAILURE)
+        .stdout("abc\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--invalid=warn", "abc"])
+        .assert()
+        .success()
+        .stdout("abc\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--invalid=ignore", "abc"])
+        .assert()
+        .success()
+        .stdout("abc\n")
+        .stderr("");
+    Ok(())
+}
+
+#[test]
+fn streaming_pipe() -> Result<(), Box<dyn std::error::Error>> {
+    // blank lines are kept and do not stop the processing
+    let stdout = pipe_command(
+        "printf",
+        vec!["size\\n1000\\n\\n2000\\n".to_string()],
+        BIN_NUMFMT,
+        vec!["--header=1".to_string(), "--to=si".to_string()],
+    )?;
+    assert_eq!(stdout, "size\n1.0K\n\n2.0K\n");
+
+    let stdout = pipe_command(
+        "printf",
+        vec!["1000\\0002000\\000".to_string()],
+        BIN_NUMFMT,
+        vec!["-z".to_string(), "--to=si".to_string()],
+    )?;
+    assert_eq!(stdout, "1.0K\u{0}2.0K\u{0}");
+    Ok(())
+}
+
+#[test]
+fn multiple_numbers() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=si", "1000", "2000", "3000"])
+        .assert()
+        .success()
+        .stdout("1.0K\n2.0K\n3.0K\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--invalid=fail", "1", "x", "2"])
+        .assert()
+        .failure()
+        .code(EXIT_CONVERSION_FAILURE)
+        .stdout("1\nx\n2\n")
+        .stderr("numfmt: invalid number: 'x' (line 2, column 1)\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["1", "x", "2"])
+        .assert()
+        .failure()
+        .code(EXIT_CONVERSION_FAILURE)
+        .stdout("1\n");
+    Ok(())
+}
+
+#[test]
+fn format_option() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--format=<%5f>", "--to=si", "1000"])
+        .assert()
+        .success()
+        .stdout("< 1.0K>\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "en_US.UTF-8")
+        .args(["--format=%'f", "1000000"])
+        .assert()
+        .success()
+        .stdout("1,000,000\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--format=%f %f", "1"]).assert().failure();
+    Ok(())
+}
+
+#[test]
+fn unit_none() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=none", "1K"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: rejecting suffix in input: '1K' (line 1, column 1) (consider using --from)\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", "--to=none", "--to-unit=1000", "1.5M"]);
+    cmd.assert().success().stdout("1500\n");
+    Ok(())
+}
+
+#[test]
+fn unit_sizes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from-unit=1Ki", "--to=si", "1000"]);
+    cmd.assert().success().stdout("1.1M\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from-unit=512", "--to-unit=1K", "4"]);
+    cmd.assert().success().stdout("3\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from-unit=1Y", "1"]);
+    cmd.assert().success().stdout("1000000000000000000000000\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to-unit=1Y", "3000000000000000000000000"]);
+    cmd.assert().success().stdout("3\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from-unit=1.5", "4"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn field_lists() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--field=5", "--to=iec", "--", "total 8", "-rw 1 a b 2048 x"]);
+    cmd.assert().success().stdout("total 8\n-rw 1 a b 2.0K x\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--field=5", "--debug", "--invalid=fail", "total 8"]);
+    cmd.assert().success().stdout("total 8\n").stderr(
+        "numfmt: input line is too short, no numbers found to convert in field 5 (line 1)\n",
+    );
+
+    let stdout = pipe_command(
+        "echo",
+        vec!["1000 2000 3000 4000 5000".to_string()],
+        BIN_NUMFMT,
+        vec!["--to=si".to_string(), "--field=2,4-".to_string()],
+    )?;
+    assert_eq!(stdout, "1000 2.0K 3000 4.0K 5.0K\n");
+
+    // blanks before a field keep the columns aligned
+    let stdout = pipe_command(
+        "echo",
+        vec!["a      1000  x".to_string()],
+        BIN_NUMFMT,
+        vec!["--to=si".to_string(), "--field=2".to_string()],
+    )?;
+    assert_eq!(stdout, "a      1.0K  x\n");
+
+    let stdout = pipe_command(
+        "echo",
+        vec!["a,,3000".to_string()],
+        BIN_NUMFMT,
+        vec!["-d,".to_string(), "--to=si".to_string(), "--field=3".to_string()],
+    )?;
+    assert_eq!(stdout, "a,,3.0K\n");
+    Ok(())
+}
+
+#[test]
+fn exact_integers() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=iec", "16Ei", "1Yi"]);
+    cmd.assert()
+        .success()
+        .stdout("18446744073709551616\n1208925819614629174706176\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "en_US.UTF-8");
+    cmd.args(["--from=si", "--to-unit=1K", "--grouping", "123456789.123456789E"]);
+    cmd.assert().success().stdout("123,456,789,123,456,789,000,000\n");
+    Ok(())
+}
+
+#[test]
+fn large_suffixes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", "5E", "7Y"]);
+    cmd.assert()
+        .success()
+        .stdout("5000000000000000000\n7000000000000000000000000\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=iec", "--to=iec", "3Zi", "1023Yi"]);
+    cmd.assert().success().stdout("3.0Z\n1023Y\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=iec", "--to=iec", "4096Yi"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: value too large to be printed: '4096Yi' (line 1, column 1)\n");
+
+    let huge = format!("1{}Y", "0".repeat(300));
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", &huge]);
+    cmd.assert()
+        .code(2)
+        .stderr(format!("numfmt: value too large to be converted: '{}' (line 1, column 1)\n", huge));
+    Ok(())
+}
+
+#[test]
+fn extended_prefixes() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", "--to=iec-i", "--extended-prefixes", "1R", "2Q"]);
+    cmd.assert().success().stdout("828Yi\n1.6Qi\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", "1R"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid suffix in input: '1R' (line 1, column 1)\n");
+    Ok(())
+}
+
+#[test]
+fn sub_units() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=si", "--sub-units", "0.000123", "250m", "12n"]);
+    cmd.assert().success().stdout("123u\n250m\n12n\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=si", "1500m", "3µ"]);
+    cmd.assert().success().stdout("1.500\n0.000003\n");
+    Ok(())
+}
+
+#[test]
+fn k8s_quantities() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=k8s", "--to=k8s", "1.5Gi", "0.5", "1.5G", "12e6"]);
+    cmd.assert().success().stdout("1536Mi\n500m\n1500M\n12e6\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=k8s", "--to=iec-i", "512Mi", "250m"]);
+    cmd.assert().success().stdout("512Mi\n0\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=iec", "--to=k8s", "1.5G", "2000"]);
+    cmd.assert().success().stdout("1536Mi\n2k\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=k8s", "1K"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid suffix in input: '1K' (line 1, column 1)\n");
+    Ok(())
+}
+
+#[test]
+fn suffix_case() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--suffix-case=insensitive", "1k", "1KI", "2g", "1M"]);
+    cmd.assert().success().stdout("1000\n1024\n2000000000\n1000000\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--suffix-case=insensitive", "1m"]);
+    cmd.assert().code(2).stderr(
+        "numfmt: ambiguous suffix in input: '1m' (milli or mega, pico or peta) (line 1, column 1)\n",
+    );
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=iec", "--suffix-case=insensitive", "3m"]);
+    cmd.assert().success().stdout("3145728\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["1k"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid suffix in input: '1k' (line 1, column 1)\n");
+    Ok(())
+}
+
+#[test]
+fn base_units() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=auto", "--to=iec-i", "1.5GB", "10Gbps", "4KiB/s"]);
+    cmd.assert().success().stdout("1.4GiB\n9.4Gibps\n4.0KiB/s\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["-d,", "--field=-", "--from=auto", "--to=si", "300 MiB,2500 Hz"]);
+    cmd.assert().success().stdout("315MB,2.5KHz\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["-d,", "--from=si", "1 K"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid suffix in input: '1 K' (line 1, column 1)\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=none", "12B"]);
+    cmd.assert().code(2);
+    Ok(())
+}
+
+#[test]
+fn to_base_unit() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to-base-unit=bytes", "--to=si", "--precision=2", "10Gbps"]);
+    cmd.assert().success().stdout("1.25GB/s\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to-base-unit=bits", "--to=si", "512MiB", "125MB"]);
+    cmd.assert().success().stdout("4.3Gbit\n1.0Gbit\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to-base-unit=nibbles", "1B"]);
+    cmd.assert().failure();
+    Ok(())
+}
+
+#[test]
+fn exponent_notation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["1e6", "2.5E-3", "+3e+2", "1E"]);
+    cmd.assert().success().stdout("1000000\n0.0025\n300\n1000000000000000000\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=si", "1.5e9"]);
+    cmd.assert().success().stdout("1.5G\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["1e"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid suffix in input: '1e' (line 1, column 1)\n");
+    Ok(())
+}
+
+#[test]
+fn negative_numbers() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=si", "--", "-2000", "-1.5M"]);
+    cmd.assert().success().stdout("-2.0K\n-1.5M\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=iec", "--round=up", "--padding=6", "--", "-1500", "-0.4"]);
+    cmd.assert().success().stdout(" -1.4K\n     0\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--from=k8s", "--to=k8s", "--", "-1.5Gi"]);
+    cmd.assert().success().stdout("-1536Mi\n");
+    Ok(())
+}
+
+#[test]
+fn locales() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "de_DE.UTF-8").env("LANG", "en_US.UTF-8");
+    cmd.args(["--to=si", "--from=si", "1,5K", "2500"]);
+    cmd.assert().success().stdout("1,5K\n2,5K\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "de_DE.UTF-8");
+    cmd.args(["1.234"]);
+    cmd.assert().failure();
+
+    let mut cmd = numfmt_command()?;
+    cmd.env_remove("LC_ALL").env("LC_NUMERIC", "C").env("LANG", "en_US.UTF-8");
+    cmd.args(["--grouping", "1234567"]);
+    cmd.assert().success().stdout("1234567\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env_remove("LC_ALL").env_remove("LC_NUMERIC").env("LANG", "fr_FR.UTF-8");
+    cmd.args(["--grouping", "1234567"]);
+    cmd.assert().success().stdout("1\u{202f}234\u{202f}567\n");
+    Ok(())
+}
+
+#[test]
+fn grouping() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "en_US.UTF-8");
+    cmd.args(["--grouping", "--", "1234.5", "-1234567"]);
+    cmd.assert().success().stdout("1,234.5\n-1,234,567\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "hi_IN.UTF-8");
+    cmd.args(["--grouping", "123456789.25"]);
+    cmd.assert().success().stdout("12,34,56,789.25\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "de_DE.UTF-8");
+    cmd.args(["--grouping", "--grouping-separator= ", "1234567,5"]);
+    cmd.assert().success().stdout("1 234 567,5\n");
+
+    // the no-break spaces of fr_FR count as one character each
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "fr_FR.UTF-8");
+    cmd.args(["--grouping", "--padding=12", "1234567"]);
+    cmd.assert().success().stdout("   1\u{202f}234\u{202f}567\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "fr_FR.UTF-8");
+    cmd.args(["--format=%'015f", "1234567"]);
+    cmd.assert().success().stdout("0000001\u{202f}234\u{202f}567\n");
+    Ok(())
+}
+
+#[test]
+fn accept_grouping() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "en_US.UTF-8");
+    cmd.args(["--accept-grouping", "--grouping", "--to=si", "1,234,567", "1,234.5K"]);
+    cmd.assert().success().stdout("1.3M\n1.3M\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "fr_FR.UTF-8");
+    cmd.args(["-d;", "--field=-", "--accept-grouping", "1 234 567;1 234,5"]);
+    cmd.assert().success().stdout("1234567;1234,5\n");
+
+    // digit groups after a space stay in the field with the default delimiter
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "fr_FR.UTF-8");
+    cmd.args(["--field=-", "--accept-grouping", "--to=si", "1 234 567 12 34"]);
+    cmd.assert().success().stdout("1,3M 12 34\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.env("LC_ALL", "en_US.UTF-8");
+    cmd.args(["--accept-grouping", "12,34,5"]);
+    cmd.assert()
+        .code(2)
+        .stderr("numfmt: invalid number: '12,34,5' (line 1, column 1)\n");
+    Ok(())
+}
+
+#[test]
+fn to_auto() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=auto", "1Ki", "2048Ki", "3M", "3000K", "1500"]);
+    cmd.assert().success().stdout("1.0Ki\n2.0Mi\n3.0M\n3.0M\n1500\n");
+
+    let mut cmd = numfmt_command()?;
+    cmd.args(["--to=auto", "--field=-", "-d,", "1Ki,3000K,12"]);
+    cmd.assert().success().stdout("1.0Ki,3.0M,12\n");
+    Ok(())
+}